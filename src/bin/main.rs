@@ -1,20 +1,196 @@
+use datafusion::config::TableParquetOptions;
+use datafusion::parquet::file::properties::WriterPropertiesBuilder;
 use datafusion::prelude::*;
-use dfkit::commands::{cat, convert, count, describe, dfsplit, query, reverse, schema, sort, view, dedup};
-use dfkit::utils::{DfKitError, parse_file_list};
+use dfkit::commands::{
+    cat, convert, count, dedup, describe, dfcast, dfsplit, diff, fillna, flatten, mutate, nest,
+    parquet_meta, pivot, query, rename, replace, reverse, schema, schema_diff, sort, top, unpivot,
+    validate, view,
+};
+use dfkit::utils::{
+    DfKitError, FileFormat, FillStrategy, NameStyle, ReadOptions, SplitMode, WriteOptions,
+    build_runtime_env, format_from_extension, load_fixed_width_layout, load_schema_file,
+    parse_byte_size, parse_file_list, parse_name_style, parse_type_overrides,
+};
 use std::env;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::sync::Arc;
 use structopt::StructOpt;
 
+/// Pool bytes per row of a batch when --memory-limit sizes the batches.
+const POOL_BYTES_PER_BATCH_ROW: usize = 16 * 1024;
+const MIN_SPILL_BATCH_SIZE: usize = 64;
+
 #[derive(StructOpt, Debug)]
 #[structopt(
     name = "dfkit",
-    about = "A fast SQL-based CLI tool for working with CSV, Parquet, and JSON data files."
+    about = "A fast SQL-based CLI tool for working with CSV, Parquet, JSON, Avro, Excel, SQLite and \
+             fixed-width data files."
 )]
 pub struct Cli {
+    #[structopt(
+        long,
+        global = true,
+        parse(from_os_str),
+        help = "Column types file, as written by schema --export"
+    )]
+    pub schema: Option<PathBuf>,
+    #[structopt(
+        long,
+        global = true,
+        help = "Column types to read with or cast to, e.g. id:Int64"
+    )]
+    pub types: Option<String>,
+    #[structopt(long, global = true, help = "Rename input columns to snake_case")]
+    pub normalize_names: bool,
+    #[structopt(long, global = true, help = "Dotted path to the records in JSON input")]
+    pub json_path: Option<String>,
+    #[structopt(long, global = true, help = "Write JSON output as a single array")]
+    pub json_array: bool,
+    #[structopt(
+        long,
+        global = true,
+        help = "Spreadsheet sheet name or zero-based index"
+    )]
+    pub sheet: Option<String>,
+    #[structopt(long, global = true, help = "Spreadsheet cell range, e.g. A1:F100")]
+    pub range: Option<String>,
+    #[structopt(long, global = true, help = "SQLite table to read or write")]
+    pub table: Option<String>,
+    #[structopt(
+        long,
+        global = true,
+        parse(from_os_str),
+        help = "JSON column layout for fixed-width files"
+    )]
+    pub layout: Option<PathBuf>,
+    #[structopt(
+        long,
+        global = true,
+        parse(try_from_str = parse_byte_size),
+        help = "Memory to use before spilling to disk, e.g. 4GB"
+    )]
+    pub memory_limit: Option<usize>,
+    #[structopt(
+        long,
+        global = true,
+        parse(from_os_str),
+        help = "Directory for spill files"
+    )]
+    pub spill_dir: Option<PathBuf>,
+    #[structopt(long, global = true, env = "DFKIT_THREADS", help = "Worker threads")]
+    pub threads: Option<NonZeroUsize>,
+    #[structopt(
+        long,
+        global = true,
+        env = "DFKIT_TARGET_PARTITIONS",
+        help = "Partitions per query plan"
+    )]
+    pub target_partitions: Option<NonZeroUsize>,
+    #[structopt(
+        long,
+        global = true,
+        env = "DFKIT_BATCH_SIZE",
+        help = "Rows per record batch"
+    )]
+    pub batch_size: Option<NonZeroUsize>,
+    #[structopt(flatten)]
+    pub parquet: ParquetArgs,
     #[structopt(subcommand)]
     pub command: Commands,
 }
 
+// Writer settings for every Parquet file dfkit produces. A doc comment here
+// would replace the top-level about text.
+#[derive(StructOpt, Debug)]
+pub struct ParquetArgs {
+    #[structopt(
+        long,
+        global = true,
+        help = "zstd(LEVEL), snappy, gzip(LEVEL), lz4 or none"
+    )]
+    pub parquet_compression: Option<String>,
+    #[structopt(long, global = true, help = "Maximum rows per Parquet row group")]
+    pub row_group_size: Option<NonZeroUsize>,
+    #[structopt(long, global = true, help = "Dictionary-encode Parquet columns")]
+    pub dictionary: bool,
+    #[structopt(
+        long,
+        global = true,
+        conflicts_with = "dictionary",
+        help = "Write Parquet columns without dictionary encoding"
+    )]
+    pub no_dictionary: bool,
+    #[structopt(
+        long,
+        global = true,
+        possible_values = &["page", "chunk", "none"],
+        help = "Parquet statistics level"
+    )]
+    pub statistics: Option<String>,
+    #[structopt(
+        long,
+        global = true,
+        use_delimiter = true,
+        help = "Columns to write Parquet bloom filters for"
+    )]
+    pub bloom_filter: Vec<String>,
+    #[structopt(
+        long,
+        global = true,
+        possible_values = &["1.0", "2.0"],
+        help = "Parquet writer version"
+    )]
+    pub writer_version: Option<String>,
+    #[structopt(
+        long,
+        global = true,
+        number_of_values = 1,
+        help = "key=value metadata entry"
+    )]
+    pub parquet_metadata: Vec<String>,
+}
+
+impl ParquetArgs {
+    fn into_options(self) -> Result<TableParquetOptions, DfKitError> {
+        let mut options = TableParquetOptions::default();
+        if let Some(compression) = self.parquet_compression {
+            options.global.compression = Some(compression.to_lowercase());
+        }
+        if let Some(rows) = self.row_group_size {
+            options.global.max_row_group_size = rows.get();
+        }
+        if self.dictionary || self.no_dictionary {
+            options.global.dictionary_enabled = Some(self.dictionary);
+        }
+        if let Some(statistics) = self.statistics {
+            options.global.statistics_enabled = Some(statistics);
+        }
+        if let Some(version) = self.writer_version {
+            options.global.writer_version = version;
+        }
+        for column in self.bloom_filter {
+            options
+                .column_specific_options
+                .entry(column)
+                .or_default()
+                .bloom_filter_enabled = Some(true);
+        }
+        for entry in self.parquet_metadata {
+            let (key, value) = entry.split_once('=').ok_or_else(|| {
+                DfKitError::CustomError(format!("Expected key=value, got: {}", entry))
+            })?;
+            options
+                .key_value_metadata
+                .insert(key.to_string(), Some(value.to_string()));
+        }
+
+        // Surface an invalid codec or level now rather than after the query has run
+        WriterPropertiesBuilder::try_from(&options.clone().with_skip_arrow_metadata(true))?;
+        Ok(options)
+    }
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(name = "dfkit")]
 pub enum Commands {
@@ -36,7 +212,7 @@ pub enum Commands {
         output: Option<PathBuf>,
     },
 
-    #[structopt(about = "Convert file format (CSV, Parquet, JSON)")]
+    #[structopt(about = "Convert a file to CSV, Parquet, JSON, xlsx or a SQLite table")]
     Convert {
         #[structopt(parse(from_os_str))]
         filename: PathBuf,
@@ -54,6 +230,10 @@ pub enum Commands {
     Schema {
         #[structopt(parse(from_os_str))]
         filename: PathBuf,
+        #[structopt(long, parse(from_os_str))]
+        export: Option<PathBuf>,
+        #[structopt(long, conflicts_with = "export")]
+        native: bool,
     },
 
     #[structopt(about = "Count the number of rows in a file")]
@@ -66,7 +246,12 @@ pub enum Commands {
     Sort {
         #[structopt(parse(from_os_str))]
         filename: PathBuf,
-        #[structopt(short, long, use_delimiter = true)]
+        #[structopt(
+            short,
+            long,
+            use_delimiter = true,
+            help = "Columns to sort by, each optionally followed by asc|desc and nulls first|last"
+        )]
         columns: Vec<String>,
         #[structopt(short, long)]
         descending: bool,
@@ -82,12 +267,26 @@ pub enum Commands {
         output: Option<PathBuf>,
     },
 
-    #[structopt(about = "Split a file into N chunks")]
+    #[structopt(about = "Split a file into chunks by count, rows, size, or column value")]
     Split {
         #[structopt(parse(from_os_str))]
         filename: PathBuf,
-        #[structopt(short, long)]
-        chunks: usize,
+        #[structopt(short, long, required_unless_one = &["rows", "max-size", "by"])]
+        chunks: Option<usize>,
+        #[structopt(long, conflicts_with_all = &["chunks", "max-size", "by"])]
+        rows: Option<usize>,
+        #[structopt(
+            long,
+            parse(try_from_str = parse_byte_size),
+            conflicts_with_all = &["chunks", "rows", "by"]
+        )]
+        max_size: Option<usize>,
+        #[structopt(long, conflicts_with_all = &["chunks", "rows", "max-size"])]
+        by: Option<String>,
+        #[structopt(long)]
+        template: Option<String>,
+        #[structopt(long, parse(try_from_str = format_from_extension))]
+        format: Option<FileFormat>,
         #[structopt(short, long)]
         output: Option<PathBuf>,
     },
@@ -98,6 +297,10 @@ pub enum Commands {
         files: Option<String>,
         #[structopt(long, required_unless = "files")]
         dir: Option<PathBuf>,
+        #[structopt(long)]
+        union_by_name: bool,
+        #[structopt(long)]
+        add_source_column: Option<String>,
         #[structopt(short, long, parse(from_os_str))]
         output: PathBuf,
     },
@@ -106,18 +309,293 @@ pub enum Commands {
     Dedup {
         #[structopt(short, long, parse(from_os_str))]
         filename: PathBuf,
+        #[structopt(long, use_delimiter = true)]
+        on: Vec<String>,
+        #[structopt(long, default_value = "first", possible_values = &["first", "last", "none"])]
+        keep: String,
+        #[structopt(long, use_delimiter = true)]
+        order_by: Vec<String>,
+        #[structopt(long, parse(from_os_str))]
+        report: Option<PathBuf>,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Pivot long data into a wide table")]
+    Pivot {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(short, long, use_delimiter = true)]
+        index: Vec<String>,
+        #[structopt(short, long)]
+        columns: String,
+        #[structopt(short, long)]
+        values: String,
+        #[structopt(short, long, default_value = "sum")]
+        agg: String,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Unpivot (melt) wide data into a long table")]
+    Unpivot {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(long, use_delimiter = true)]
+        id: Vec<String>,
+        #[structopt(long, use_delimiter = true)]
+        value_columns: Vec<String>,
+        #[structopt(long, default_value = "variable")]
+        var_name: String,
+        #[structopt(long, default_value = "value")]
+        value_name: String,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Compare two files by key and report added, removed, and changed rows")]
+    Diff {
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+        #[structopt(parse(from_os_str))]
+        new: PathBuf,
+        #[structopt(short, long, use_delimiter = true)]
+        key: Vec<String>,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Compare the schemas of two files")]
+    SchemaDiff {
+        #[structopt(parse(from_os_str))]
+        left: PathBuf,
+        #[structopt(parse(from_os_str))]
+        right: PathBuf,
+        #[structopt(
+            long,
+            default_value = "never",
+            possible_values = &["never", "info", "warning", "breaking"]
+        )]
+        fail_on: String,
+    },
+
+    #[structopt(about = "Validate a file against a YAML rules file")]
+    Validate {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(short, long, parse(from_os_str))]
+        rules: PathBuf,
+        #[structopt(long, parse(from_os_str))]
+        failures: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Convert columns to the types given with --types")]
+    Cast {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(long)]
+        safe: bool,
+        #[structopt(long)]
+        datetime_format: Option<String>,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Add or replace columns computed from SQL expressions")]
+    Mutate {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(long = "set", number_of_values = 1, required = true)]
+        set: Vec<String>,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Fill null values with a constant or a fill strategy")]
+    Fillna {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(long, use_delimiter = true)]
+        columns: Vec<String>,
+        #[structopt(long, required_unless = "strategy", conflicts_with = "strategy")]
+        value: Option<String>,
+        #[structopt(
+            long,
+            possible_values = &["forward", "backward", "mean", "median", "mode"]
+        )]
+        strategy: Option<String>,
+        #[structopt(long, use_delimiter = true)]
+        order_by: Vec<String>,
+        #[structopt(long, use_delimiter = true)]
+        partition_by: Vec<String>,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Replace values in a column using an old:new mapping")]
+    Replace {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(short, long)]
+        column: String,
+        #[structopt(short, long)]
+        map: String,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Rename columns or normalize all column names")]
+    Rename {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(short, long, required_unless = "normalize")]
+        map: Option<String>,
+        #[structopt(
+            long,
+            possible_values = &["snake_case", "lower", "upper"],
+            parse(try_from_str = parse_name_style)
+        )]
+        normalize: Option<NameStyle>,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Show the top N rows by a column, overall or per group")]
+    Top {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(short, long, default_value = "10")]
+        n: usize,
+        #[structopt(short, long)]
+        by: String,
+        #[structopt(short, long, use_delimiter = true)]
+        group_by: Vec<String>,
+        #[structopt(short, long)]
+        ascending: bool,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Show Parquet file, row group and column chunk metadata")]
+    ParquetMeta {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(long, default_value = "table", possible_values = &["table", "json"])]
+        output_format: String,
+    },
+
+    #[structopt(about = "Flatten struct columns into parent.child columns and explode lists")]
+    Flatten {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(long, default_value = ".")]
+        separator: String,
+        #[structopt(long)]
+        max_depth: Option<usize>,
+        #[structopt(
+            long,
+            use_delimiter = true,
+            help = "List columns to explode into a row per element; empty lists keep a null row"
+        )]
+        explode: Vec<String>,
         #[structopt(short, long, parse(from_os_str))]
         output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Group parent.child columns back into struct columns")]
+    Nest {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(long, default_value = ".")]
+        separator: String,
+        #[structopt(short, long, use_delimiter = true, requires = "into")]
+        columns: Vec<String>,
+        #[structopt(long, requires = "columns")]
+        into: Option<String>,
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+}
+
+fn main() -> Result<(), DfKitError> {
+    let cli = Cli::from_args();
+
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    runtime.enable_all();
+    if let Some(threads) = cli.threads {
+        runtime.worker_threads(threads.get());
     }
+    runtime.build()?.block_on(run(cli))
 }
 
-#[tokio::main]
-async fn main() -> Result<(), DfKitError> {
-    let cmd = Commands::from_args();
-    let config = SessionConfig::new().with_information_schema(true);
-    let ctx = SessionContext::new_with_config(config);
+async fn run(mut cli: Cli) -> Result<(), DfKitError> {
+    // `cast` converts to --types itself rather than reading the file with them
+    let cast_types = match cli.command {
+        Commands::Cast { .. } => cli.types.take(),
+        _ => None,
+    };
+
+    // Inline --types take precedence over a --schema file
+    let mut schema_overrides = vec![];
+    if let Some(types) = &cli.types {
+        schema_overrides.extend(parse_type_overrides(types)?);
+    }
+    if let Some(path) = &cli.schema {
+        schema_overrides.extend(load_schema_file(path)?);
+    }
+    let layout = match &cli.layout {
+        Some(path) => Some(load_fixed_width_layout(path)?),
+        None => None,
+    };
+    let read_options = ReadOptions {
+        schema_overrides,
+        normalize_names: cli.normalize_names.then_some(NameStyle::SnakeCase),
+        json_path: cli.json_path.clone(),
+        sheet: cli.sheet.clone(),
+        range: cli.range.clone(),
+        table: cli.table.clone(),
+        layout,
+    };
+
+    // A bounded pool makes sorts, joins and aggregations spill instead of running out of memory
+    let runtime = build_runtime_env(cli.memory_limit, cli.spill_dir.clone())?;
+
+    let write_options = WriteOptions {
+        parquet: cli.parquet.into_options()?,
+        json_array: cli.json_array,
+        table: cli.table.clone(),
+    };
+
+    let mut config = SessionConfig::new()
+        .with_information_schema(true)
+        .with_extension(Arc::new(read_options))
+        .with_extension(Arc::new(write_options));
+    // Without --target-partitions, plans fan out to as many partitions as worker threads
+    if let Some(partitions) = cli.target_partitions.or(cli.threads) {
+        config = config.with_target_partitions(partitions.get());
+    }
+    if let Some(batch_size) = cli.batch_size {
+        config = config.with_batch_size(batch_size.get());
+    }
+    if let Some(limit) = cli.memory_limit {
+        let share = limit / config.target_partitions();
+        let execution = &mut config.options_mut().execution;
+        // Keep the merge reservation small enough that a tight pool still leaves room to sort
+        execution.sort_spill_reservation_bytes =
+            execution.sort_spill_reservation_bytes.min(share / 4);
+        execution.sort_in_place_threshold_bytes =
+            execution.sort_in_place_threshold_bytes.min(share / 16);
+        // Merging spill files holds a batch from every file at once, so the smaller the
+        // pool, the smaller the batches need to be for the final merge to fit
+        if cli.batch_size.is_none() {
+            execution.batch_size = execution
+                .batch_size
+                .min((share / POOL_BYTES_PER_BATCH_ROW).max(MIN_SPILL_BATCH_SIZE));
+        }
+    }
+    let ctx = SessionContext::new_with_config_rt(config, runtime);
 
-    match cmd {
+    match cli.command {
         Commands::View { filename, limit } => {
             view(&ctx, &filename, limit).await?;
         }
@@ -137,8 +615,12 @@ async fn main() -> Result<(), DfKitError> {
         Commands::Describe { filename } => {
             describe(&ctx, &filename).await?;
         }
-        Commands::Schema { filename } => {
-            schema(&ctx, &filename).await?;
+        Commands::Schema {
+            filename,
+            export,
+            native,
+        } => {
+            schema(&ctx, &filename, export, native).await?;
         }
         Commands::Count { filename } => {
             count(&ctx, &filename).await?;
@@ -157,17 +639,190 @@ async fn main() -> Result<(), DfKitError> {
         Commands::Split {
             filename,
             chunks,
+            rows,
+            max_size,
+            by,
+            template,
+            format,
             output,
         } => {
+            let mode = if let Some(rows) = rows {
+                SplitMode::Rows(rows)
+            } else if let Some(size) = max_size {
+                SplitMode::MaxSize(size)
+            } else if let Some(column) = by {
+                SplitMode::By(column)
+            } else {
+                SplitMode::Chunks(chunks.unwrap_or(1))
+            };
             let out_dir = output.unwrap_or_else(|| env::current_dir().unwrap());
-            dfsplit(&ctx, &filename, chunks, &out_dir).await?;
+            dfsplit(&ctx, &filename, mode, template, format, &out_dir).await?;
         }
-        Commands::Cat { files, dir, output } => {
+        Commands::Cat {
+            files,
+            dir,
+            union_by_name,
+            add_source_column,
+            output,
+        } => {
             let file_list = parse_file_list(files, dir)?;
-            cat(&ctx, file_list, &output).await?;
+            cat(&ctx, file_list, union_by_name, add_source_column, &output).await?;
         }
-        Commands::Dedup { filename, output } => {
-            dedup(&ctx, &filename, output).await?;
+        Commands::Dedup {
+            filename,
+            on,
+            keep,
+            order_by,
+            report,
+            output,
+        } => {
+            dedup(&ctx, &filename, &on, &keep, &order_by, report, output).await?;
+        }
+        Commands::Pivot {
+            filename,
+            index,
+            columns,
+            values,
+            agg,
+            output,
+        } => {
+            pivot(&ctx, &filename, &index, &columns, &values, &agg, output).await?;
+        }
+        Commands::Unpivot {
+            filename,
+            id,
+            value_columns,
+            var_name,
+            value_name,
+            output,
+        } => {
+            unpivot(
+                &ctx,
+                &filename,
+                &id,
+                &value_columns,
+                &var_name,
+                &value_name,
+                output,
+            )
+            .await?;
+        }
+        Commands::Diff {
+            old,
+            new,
+            key,
+            output,
+        } => {
+            diff(&ctx, &old, &new, &key, output).await?;
+        }
+        Commands::SchemaDiff {
+            left,
+            right,
+            fail_on,
+        } => {
+            schema_diff(&ctx, &left, &right, &fail_on).await?;
+        }
+        Commands::Validate {
+            filename,
+            rules,
+            failures,
+        } => {
+            validate(&ctx, &filename, &rules, failures).await?;
+        }
+        Commands::Cast {
+            filename,
+            safe,
+            datetime_format,
+            output,
+        } => {
+            let types = cast_types
+                .ok_or_else(|| DfKitError::CustomError("cast requires --types".into()))?;
+            dfcast(&ctx, &filename, &types, safe, datetime_format, output).await?;
+        }
+        Commands::Mutate {
+            filename,
+            set,
+            output,
+        } => {
+            mutate(&ctx, &filename, &set, output).await?;
+        }
+        Commands::Fillna {
+            filename,
+            columns,
+            value,
+            strategy,
+            order_by,
+            partition_by,
+            output,
+        } => {
+            let strategy = match (value, strategy.as_deref()) {
+                (Some(value), _) => FillStrategy::Value(value),
+                (None, Some("forward")) => FillStrategy::Forward,
+                (None, Some("backward")) => FillStrategy::Backward,
+                (None, Some("mean")) => FillStrategy::Mean,
+                (None, Some("median")) => FillStrategy::Median,
+                _ => FillStrategy::Mode,
+            };
+            fillna(
+                &ctx,
+                &filename,
+                &columns,
+                strategy,
+                &order_by,
+                &partition_by,
+                output,
+            )
+            .await?;
+        }
+        Commands::Replace {
+            filename,
+            column,
+            map,
+            output,
+        } => {
+            replace(&ctx, &filename, &column, &map, output).await?;
+        }
+        Commands::Rename {
+            filename,
+            map,
+            normalize,
+            output,
+        } => {
+            rename(&ctx, &filename, map, normalize, output).await?;
+        }
+        Commands::Top {
+            filename,
+            n,
+            by,
+            group_by,
+            ascending,
+            output,
+        } => {
+            top(&ctx, &filename, n, &by, &group_by, ascending, output).await?;
+        }
+        Commands::ParquetMeta {
+            filename,
+            output_format,
+        } => {
+            parquet_meta(&ctx, &filename, &output_format).await?;
+        }
+        Commands::Flatten {
+            filename,
+            separator,
+            max_depth,
+            explode,
+            output,
+        } => {
+            flatten(&ctx, &filename, &separator, max_depth, &explode, output).await?;
+        }
+        Commands::Nest {
+            filename,
+            separator,
+            columns,
+            into,
+            output,
+        } => {
+            nest(&ctx, &filename, &separator, &columns, into, output).await?;
         }
     }
 
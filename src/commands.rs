@@ -1,8 +1,44 @@
-use crate::utils::{DfKitError, file_type, register_table, write_output};
-use datafusion::arrow::compute::concat_batches;
+use crate::utils::{
+    BatchWriter, DfKitError, FileFormat, FileParseError, FillStrategy, NameStyle, ReadOptions,
+    SplitMode, StorageType, apply_schema_overrides, download_to_tempfile, file_type, input_format,
+    load_validation_rules, matches_pattern, normalize_columns, parse_sort_spec,
+    parse_type_overrides, quote_identifier, quote_literal, read_parquet_metadata,
+    register_secondary_table, register_table, render_template, sqlite_table_sql, storage_type,
+    write_output, write_schema_file,
+};
+use apache_avro::Reader as AvroReader;
+use chrono::{NaiveDate, NaiveDateTime};
+use datafusion::arrow::array::{
+    Array, ArrayRef, AsArray, BooleanArray, Date32Array, Int64Array, RecordBatch, StringArray,
+    TimestampMicrosecondArray, TimestampMillisecondArray, TimestampNanosecondArray,
+    TimestampSecondArray, UInt32Array, UInt64Array,
+};
+use datafusion::arrow::compute::{
+    CastOptions, cast as arrow_cast, cast_with_options, take_record_batch,
+};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::ipc::reader::FileReader;
+use datafusion::arrow::ipc::writer::FileWriter;
+use datafusion::arrow::util::display::array_value_to_string;
+use datafusion::catalog::streaming::StreamingTable;
+use datafusion::common::{Column, ScalarValue, UnnestOptions};
 use datafusion::datasource::MemTable;
-use datafusion::logical_expr::col;
-use datafusion::prelude::SessionContext;
+use datafusion::execution::disk_manager::{DiskManager, RefCountedTempFile};
+use datafusion::execution::memory_pool::{MemoryConsumer, MemoryReservation};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::functions::expr_fn::named_struct;
+use datafusion::functions_nested::expr_fn::array_length;
+use datafusion::logical_expr::type_coercion::binary::type_union_resolution;
+use datafusion::logical_expr::{Expr, LogicalPlanBuilder, cast, ident, lit, when};
+use datafusion::parquet::file::reader::{FileReader as ParquetFileReader, SerializedFileReader};
+use datafusion::parquet::schema::printer::print_schema;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::streaming::PartitionStream;
+use datafusion::prelude::{CsvReadOptions, DataFrame, SessionContext};
+use futures::{StreamExt, TryStreamExt, future, stream};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -12,7 +48,7 @@ pub async fn view(
     filename: &Path,
     limit: Option<usize>,
 ) -> Result<(), DfKitError> {
-    let df = register_table(&ctx, "t", &filename).await?;
+    let df = register_table(ctx, "t", filename).await?;
     let limit = limit.unwrap_or(10);
 
     if limit > 0 {
@@ -30,9 +66,9 @@ pub async fn query(
     sql: Option<String>,
     output: Option<PathBuf>,
 ) -> Result<(), DfKitError> {
-    let file_type = file_type(&filename)?;
-    let _ = register_table(&ctx, "t", &filename).await?;
-    let df_sql = ctx.sql(&*sql.unwrap()).await?;
+    let file_type = input_format(ctx, filename)?;
+    let _ = register_table(ctx, "t", filename).await?;
+    let df_sql = ctx.sql(&sql.unwrap()).await?;
 
     if let Some(path) = output {
         write_output(df_sql, &path, &file_type).await?;
@@ -49,22 +85,38 @@ pub async fn convert(
     filename: &Path,
     output_filename: &Path,
 ) -> Result<(), DfKitError> {
-    let df = register_table(ctx, "t", &filename).await?;
-    let output_file_type = file_type(&output_filename)?;
+    let df = register_table(ctx, "t", filename).await?;
+    let output_file_type = file_type(output_filename)?;
 
-    write_output(df, &output_filename, &output_file_type).await?;
+    write_output(df, output_filename, &output_file_type).await?;
     Ok(())
 }
 
 pub async fn describe(ctx: &SessionContext, filename: &Path) -> Result<(), DfKitError> {
-    let df = register_table(&ctx, "t", &filename).await?;
+    let df = register_table(ctx, "t", filename).await?;
     let describe = df.describe().await?;
     describe.show().await?;
     Ok(())
 }
 
-pub async fn schema(ctx: &SessionContext, filename: &Path) -> Result<(), DfKitError> {
-    let _ = register_table(&ctx, "t", &filename).await?;
+pub async fn schema(
+    ctx: &SessionContext,
+    filename: &Path,
+    export: Option<PathBuf>,
+    native: bool,
+) -> Result<(), DfKitError> {
+    let df = register_table(ctx, "t", filename).await?;
+
+    if let Some(path) = export {
+        write_schema_file(df.schema().as_arrow(), &path)?;
+        println!("Schema written to: {}", path.display());
+        return Ok(());
+    }
+
+    if native {
+        return native_schema(ctx, filename, df).await;
+    }
+
     let sql = "SELECT column_name, data_type, is_nullable \
                                 FROM information_schema.columns WHERE table_name = 't'";
     let df = ctx.sql(sql).await?;
@@ -72,10 +124,168 @@ pub async fn schema(ctx: &SessionContext, filename: &Path) -> Result<(), DfKitEr
     Ok(())
 }
 
+/// Rows DataFusion reads by default when inferring CSV and JSON schemas.
+const SCHEMA_INFER_ROWS: usize = 1000;
+
+/// Prints the schema as stored in the file, followed by any Arrow metadata
+/// attached to the columns once read.
+async fn native_schema(
+    ctx: &SessionContext,
+    filename: &Path,
+    df: DataFrame,
+) -> Result<(), DfKitError> {
+    let format = input_format(ctx, filename)?;
+    if matches!(
+        format,
+        FileFormat::Avro | FileFormat::Parquet | FileFormat::Sqlite
+    ) && storage_type(filename)? != StorageType::Local
+    {
+        return Err(DfKitError::CustomError(
+            "schema --native needs a local Avro, Parquet or SQLite file".into(),
+        ));
+    }
+
+    match format {
+        FileFormat::Avro => {
+            let reader = AvroReader::new(fs::File::open(filename)?)?;
+            println!("{}", serde_json::to_string_pretty(reader.writer_schema())?);
+        }
+        FileFormat::Parquet => {
+            let reader = SerializedFileReader::new(fs::File::open(filename)?)?;
+            let mut message = vec![];
+            print_schema(&mut message, reader.metadata().file_metadata().schema());
+            print!("{}", String::from_utf8_lossy(&message));
+        }
+        FileFormat::Sqlite => {
+            let table = ctx
+                .copied_config()
+                .get_extension::<ReadOptions>()
+                .and_then(|options| options.table.clone());
+            println!("{}", sqlite_table_sql(filename, table.as_deref())?);
+        }
+        FileFormat::FixedWidth => {
+            // The layout is the only schema a fixed-width file has
+            let layout = ctx
+                .copied_config()
+                .get_extension::<ReadOptions>()
+                .and_then(|options| options.layout.clone())
+                .ok_or_else(|| {
+                    DfKitError::CustomError("Fixed-width files need a --layout file".into())
+                })?;
+            println!("{}", serde_json::to_string_pretty(&layout)?);
+        }
+        FileFormat::Csv | FileFormat::Json | FileFormat::Excel => {
+            // Text formats and spreadsheets store no schema, so show what inference saw instead
+            let schema = df.schema().as_arrow().clone();
+            let sample = match format {
+                // Re-read local CSVs as text so samples show values before type conversion
+                FileFormat::Csv if storage_type(filename)? == StorageType::Local => {
+                    let text_schema = Schema::new(
+                        schema
+                            .fields()
+                            .iter()
+                            .map(|f| Field::new(f.name(), DataType::Utf8, true))
+                            .collect::<Vec<_>>(),
+                    );
+                    let path = filename.to_string_lossy();
+                    ctx.read_csv(path.as_ref(), CsvReadOptions::new().schema(&text_schema))
+                        .await?
+                }
+                _ => df.clone(),
+            };
+            let batches = sample.limit(0, Some(SCHEMA_INFER_ROWS))?.collect().await?;
+            let sampled_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+
+            let mut null_counts = vec![0; schema.fields().len()];
+            let mut samples: Vec<Vec<String>> = vec![vec![]; schema.fields().len()];
+            for batch in &batches {
+                for (i, column) in batch.columns().iter().enumerate() {
+                    null_counts[i] += column.null_count();
+                    for row in 0..column.len() {
+                        if samples[i].len() == 3 {
+                            break;
+                        }
+                        if column.is_valid(row) {
+                            let value = array_value_to_string(column, row)?;
+                            if !samples[i].contains(&value) {
+                                samples[i].push(value);
+                            }
+                        }
+                    }
+                }
+            }
+
+            let evidence = RecordBatch::try_from_iter(vec![
+                (
+                    "column_name",
+                    Arc::new(StringArray::from_iter_values(
+                        schema.fields().iter().map(|f| f.name()),
+                    )) as ArrayRef,
+                ),
+                (
+                    "inferred_type",
+                    Arc::new(StringArray::from_iter_values(
+                        schema.fields().iter().map(|f| f.data_type().to_string()),
+                    )),
+                ),
+                (
+                    "sampled_rows",
+                    Arc::new(UInt64Array::from_value(
+                        sampled_rows as u64,
+                        schema.fields().len(),
+                    )),
+                ),
+                (
+                    "null_count",
+                    Arc::new(UInt64Array::from_iter_values(
+                        null_counts.iter().map(|n| *n as u64),
+                    )),
+                ),
+                (
+                    "sample_values",
+                    Arc::new(StringArray::from_iter_values(
+                        samples.iter().map(|s| s.join(", ")),
+                    )),
+                ),
+            ])?;
+            ctx.read_batch(evidence)?.show().await?;
+        }
+    }
+
+    let mut metadata: Vec<(String, String, String)> = vec![];
+    for field in df.schema().fields() {
+        let mut entries = field.metadata().iter().collect::<Vec<_>>();
+        entries.sort();
+        for (key, value) in entries {
+            metadata.push((field.name().clone(), key.clone(), value.clone()));
+        }
+    }
+    if !metadata.is_empty() {
+        let metadata_batch = RecordBatch::try_from_iter(vec![
+            (
+                "column_name",
+                Arc::new(StringArray::from_iter_values(metadata.iter().map(|m| &m.0)))
+                    as ArrayRef,
+            ),
+            (
+                "key",
+                Arc::new(StringArray::from_iter_values(metadata.iter().map(|m| &m.1))),
+            ),
+            (
+                "value",
+                Arc::new(StringArray::from_iter_values(metadata.iter().map(|m| &m.2))),
+            ),
+        ])?;
+        ctx.read_batch(metadata_batch)?.show().await?;
+    }
+
+    Ok(())
+}
+
 pub async fn count(ctx: &SessionContext, filename: &Path) -> Result<(), DfKitError> {
-    let _ = register_table(&ctx, "t", &filename).await?;
+    let _ = register_table(ctx, "t", filename).await?;
     let sql = "SELECT COUNT(*) FROM t";
-    let df = ctx.sql(&sql).await?;
+    let df = ctx.sql(sql).await?;
     df.show().await?;
 
     Ok(())
@@ -92,8 +302,11 @@ pub async fn sort(
 
     let sort_exprs = columns
         .iter()
-        .map(|col_name| col(col_name).sort(!descending, descending))
-        .collect();
+        .map(|spec| {
+            let spec = parse_sort_spec(spec, descending)?;
+            Ok(ident(spec.column).sort(spec.ascending, spec.nulls_first))
+        })
+        .collect::<Result<Vec<_>, DfKitError>>()?;
 
     let sorted_df = df.sort(sort_exprs)?;
 
@@ -114,23 +327,39 @@ pub async fn reverse(
     output: Option<PathBuf>,
 ) -> Result<(), DfKitError> {
     let df = register_table(ctx, "t", filename).await?;
-    let batches = df.collect().await?;
+    let schema: SchemaRef = Arc::new(df.schema().as_arrow().clone());
+    let runtime = ctx.runtime_env();
+    let mut reservation = MemoryConsumer::new("Reverse")
+        .with_can_spill(true)
+        .register(&runtime.memory_pool);
 
-    let schema = batches[0].schema();
-    let mut all_rows = vec![];
-    for batch in &batches {
-        for row in 0..batch.num_rows() {
-            all_rows.push(batch.slice(row, 1));
-        }
-    }
+    let mut buffered = vec![];
+    let mut spills = vec![];
 
-    all_rows.reverse();
+    // Partitions are consumed in order so rows keep their position in the file
+    for mut stream in df.execute_stream_partitioned().await? {
+        while let Some(batch) = stream.next().await {
+            let batch = reverse_batch(&batch?)?;
+            let size = batch.get_array_memory_size();
+            buffered.push(batch);
 
-    let reversed_batch = concat_batches(&schema, &all_rows)?;
+            // Spill once the session's memory pool can't hold the buffered rows
+            if reservation.try_grow(size).is_err() {
+                spills.push(spill_batches(&runtime.disk_manager, &schema, &buffered)?);
+                buffered.clear();
+                reservation.free();
+            }
+        }
+    }
 
-    let provider = MemTable::try_new(schema, vec![vec![reversed_batch]])?;
-    ctx.register_table("reversed", Arc::new(provider))?;
-    let reversed_df = ctx.table("reversed").await?;
+    let partition = ReversedPartition {
+        schema: Arc::clone(&schema),
+        buffered,
+        spills,
+        _reservation: reservation,
+    };
+    let provider = StreamingTable::try_new(schema, vec![Arc::new(partition)])?;
+    let reversed_df = ctx.read_table(Arc::new(provider))?;
 
     if let Some(out_path) = output {
         let format = file_type(&out_path)?;
@@ -143,92 +372,479 @@ pub async fn reverse(
     Ok(())
 }
 
+fn reverse_batch(batch: &RecordBatch) -> Result<RecordBatch, DfKitError> {
+    let indices = UInt32Array::from_iter_values((0..batch.num_rows() as u32).rev());
+    Ok(take_record_batch(batch, &indices)?)
+}
+
+fn spill_batches(
+    disk_manager: &DiskManager,
+    schema: &SchemaRef,
+    batches: &[RecordBatch],
+) -> Result<Arc<RefCountedTempFile>, DfKitError> {
+    let spill_file = disk_manager.create_tmp_file("reverse")?;
+    let mut writer = FileWriter::try_new(fs::File::create(spill_file.path())?, schema)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    Ok(Arc::new(spill_file))
+}
+
+fn read_spill(spill_file: &RefCountedTempFile) -> Result<Vec<RecordBatch>, ArrowError> {
+    let reader = FileReader::try_new(fs::File::open(spill_file.path())?, None)?;
+    reader.collect()
+}
+
+/// Yields the reversed batches newest first: the in-memory tail of the input,
+/// then each spill file from last to first, so only one spill file is held in
+/// memory at a time.
+#[derive(Debug)]
+struct ReversedPartition {
+    schema: SchemaRef,
+    buffered: Vec<RecordBatch>,
+    spills: Vec<Arc<RefCountedTempFile>>,
+    /// Keeps the in-memory tail counted against the pool until it is written.
+    _reservation: MemoryReservation,
+}
+
+impl PartitionStream for ReversedPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let in_memory = self.buffered.clone().into_iter().rev().map(Ok);
+        let spilled = self
+            .spills
+            .clone()
+            .into_iter()
+            .rev()
+            .flat_map(|spill_file| match read_spill(&spill_file) {
+                Ok(batches) => batches.into_iter().rev().map(Ok).collect::<Vec<_>>(),
+                Err(e) => vec![Err(e.into())],
+            });
+
+        Box::pin(RecordBatchStreamAdapter::new(
+            Arc::clone(&self.schema),
+            stream::iter(in_memory.chain(spilled)),
+        ))
+    }
+}
+
 pub async fn dfsplit(
     ctx: &SessionContext,
     filename: &Path,
-    chunks: usize,
+    mode: SplitMode,
+    template: Option<String>,
+    output_format: Option<FileFormat>,
     output_dir: &Path,
 ) -> Result<(), DfKitError> {
-    if chunks == 0 {
-        return Err(DfKitError::CustomError(
-            "Chunks must be greater than 0".into(),
-        ));
-    }
     let df = register_table(ctx, "t", filename).await?;
-    let total_rows = df.clone().count().await?;
-    let mut rows_per_chunk = total_rows / chunks; // in the odd case, the last chunk will fill in the rest
-    let mut remainder = total_rows % chunks;
 
-    if remainder > 0 {
-        rows_per_chunk += 1;
-    }
-
-    if chunks > total_rows {
-        return Err(DfKitError::CustomError(
-            "Chunks must be smaller than total rows".into(),
-        ));
-    }
+    // Resolve the maximum number of rows for each (1-based) chunk
+    let chunk_rows: Box<dyn Fn(usize) -> Option<usize>> = match mode {
+        SplitMode::Chunks(chunks) => {
+            if chunks == 0 {
+                return Err(DfKitError::CustomError(
+                    "Chunks must be greater than 0".into(),
+                ));
+            }
+            let total_rows = df.clone().count().await?;
+            if chunks > total_rows {
+                return Err(DfKitError::CustomError(
+                    "Chunks must be smaller than total rows".into(),
+                ));
+            }
+            // the first `remainder` chunks take one extra row each
+            let rows_per_chunk = total_rows / chunks;
+            let remainder = total_rows % chunks;
+            Box::new(move |n| Some(rows_per_chunk + usize::from(n <= remainder)))
+        }
+        SplitMode::Rows(rows) => {
+            if rows == 0 {
+                return Err(DfKitError::CustomError(
+                    "Rows must be greater than 0".into(),
+                ));
+            }
+            Box::new(move |_| Some(rows))
+        }
+        _ => Box::new(|_| None),
+    };
+    let max_size = match mode {
+        SplitMode::MaxSize(size) => Some(size),
+        _ => None,
+    };
 
     fs::create_dir_all(output_dir)?;
 
     let stem = filename.file_stem().unwrap().to_string_lossy();
-    let extension = filename.extension().unwrap_or_default().to_string_lossy();
-    let format = file_type(filename)?;
+    let format = match output_format {
+        Some(format) => format,
+        None => input_format(ctx, filename)?,
+    };
+    let template = template.unwrap_or_else(|| match mode {
+        SplitMode::By(_) => "{stem}_{value}.{ext}".to_string(),
+        _ => "{stem}_{n}.{ext}".to_string(),
+    });
+    let schema: SchemaRef = Arc::new(df.schema().as_arrow().clone());
+    let config = ctx.copied_config();
+
+    if let SplitMode::By(column) = &mode {
+        let column_index = schema.index_of(column)?;
+        // Sorting by the key brings each group together, so only one output
+        // file needs to be open at a time
+        let mut stream = df
+            .sort(vec![ident(column).sort(true, true)])?
+            .execute_stream()
+            .await?;
+
+        // Values that render to the same file name would otherwise be merged
+        let mut file_values: BTreeMap<String, Option<String>> = BTreeMap::new();
+        let mut current: Option<(Option<String>, BatchWriter)> = None;
 
-    for i in 0..chunks {
-        if remainder > 0 && i >= remainder {
-            rows_per_chunk -= 1;
-            remainder = 0;
+        while let Some(batch) = stream.next().await {
+            let batch = batch?;
+            let values = batch.column(column_index);
+            let keys = (0..batch.num_rows())
+                .map(|row| {
+                    if values.is_null(row) {
+                        Ok(None)
+                    } else {
+                        array_value_to_string(values, row).map(Some)
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut start = 0;
+            while start < keys.len() {
+                let key = &keys[start];
+                let end = keys[start..]
+                    .iter()
+                    .position(|other| other != key)
+                    .map_or(keys.len(), |offset| start + offset);
+
+                if current
+                    .as_ref()
+                    .is_none_or(|(current_key, _)| current_key != key)
+                {
+                    if let Some((_, writer)) = current.take() {
+                        let rows = writer.rows_written();
+                        let chunk_path = writer.finish()?;
+                        println!("Written {} rows to {}", rows, chunk_path.display());
+                    }
+
+                    let component = key
+                        .as_deref()
+                        .map_or_else(|| "null".to_string(), sanitize_file_component);
+                    let chunk_filename = render_template(
+                        &template,
+                        &stem,
+                        format.extension(),
+                        None,
+                        Some(&component),
+                    )?;
+                    match file_values.entry(chunk_filename) {
+                        Entry::Occupied(entry) => {
+                            let describe = |value: &Option<String>| match value {
+                                Some(value) => format!("'{}'", value),
+                                None => "NULL".to_string(),
+                            };
+                            return Err(DfKitError::CustomError(format!(
+                                "Values {} and {} of column {} both map to file name {}",
+                                describe(entry.get()),
+                                describe(key),
+                                column,
+                                entry.key()
+                            )));
+                        }
+                        Entry::Vacant(entry) => {
+                            let chunk_path = output_dir.join(entry.key());
+                            let writer =
+                                BatchWriter::try_new(&chunk_path, &format, &schema, &config)?;
+                            entry.insert(key.clone());
+                            current = Some((key.clone(), writer));
+                        }
+                    }
+                }
+
+                let (_, writer) = current.as_mut().unwrap();
+                writer.write(&batch.slice(start, end - start))?;
+                start = end;
+            }
         }
-        let offset = i * rows_per_chunk;
-        let chunk_df = df.clone().limit(offset, Some(rows_per_chunk))?;
 
-        let chunk_filename = format!("{}_{}.{}", stem, i + 1, extension);
-        let chunk_path = output_dir.join(chunk_filename);
+        if let Some((_, writer)) = current {
+            let rows = writer.rows_written();
+            let chunk_path = writer.finish()?;
+            println!("Written {} rows to {}", rows, chunk_path.display());
+        }
+
+        return Ok(());
+    }
+
+    let mut chunk = 0;
+    let mut chunk_bytes = 0;
+    let mut writer: Option<BatchWriter> = None;
 
-        write_output(chunk_df, &chunk_path, &format).await?;
+    for mut stream in df.execute_stream_partitioned().await? {
+        while let Some(batch) = stream.next().await {
+            let mut batch = batch?;
 
-        println!("Written chunk {} to {}", i + 1, chunk_path.display());
+            while batch.num_rows() > 0 {
+                if writer.is_none() {
+                    chunk += 1;
+                    chunk_bytes = 0;
+                    let chunk_filename =
+                        render_template(&template, &stem, format.extension(), Some(chunk), None)?;
+                    let chunk_path = output_dir.join(chunk_filename);
+                    writer = Some(BatchWriter::try_new(&chunk_path, &format, &schema, &config)?);
+                }
+                let current = writer.as_mut().unwrap();
+
+                let mut rows = batch.num_rows();
+                let mut full = false;
+                if let Some(limit) = chunk_rows(chunk) {
+                    rows = rows.min(limit - current.rows_written());
+                    full = current.rows_written() + rows >= limit;
+                }
+                if let Some(max_size) = max_size {
+                    // Shrink the slice until its encoded size fits in what is
+                    // left of the chunk, rolling over when not even a row fits
+                    let remaining = max_size - chunk_bytes;
+                    let mut size = current.encoded_size(&batch.slice(0, rows))?;
+                    while size > remaining {
+                        rows = (rows * remaining / size).min(rows - 1);
+                        size = current.encoded_size(&batch.slice(0, rows))?;
+                    }
+                    if rows == 0 && current.rows_written() == 0 {
+                        return Err(DfKitError::CustomError(format!(
+                            "A single row is larger than the maximum chunk size of {} bytes",
+                            max_size
+                        )));
+                    }
+                    chunk_bytes += size;
+                    full = rows == 0 || chunk_bytes == max_size;
+                }
+
+                if rows > 0 {
+                    current.write(&batch.slice(0, rows))?;
+                    if max_size.is_some() {
+                        // Each slice becomes its own row group, which keeps
+                        // the Parquet size estimate an upper bound
+                        current.flush()?;
+                    }
+                    batch = batch.slice(rows, batch.num_rows() - rows);
+                }
+
+                if full {
+                    let chunk_path = writer.take().unwrap().finish()?;
+                    println!("Written chunk {} to {}", chunk, chunk_path.display());
+                }
+            }
+        }
+    }
+
+    if let Some(current) = writer {
+        let chunk_path = current.finish()?;
+        println!("Written chunk {} to {}", chunk, chunk_path.display());
     }
 
     Ok(())
 }
 
+fn sanitize_file_component(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
 pub async fn cat(
     ctx: &SessionContext,
     files: Vec<PathBuf>,
+    union_by_name: bool,
+    source_column: Option<String>,
     out_path: &Path,
 ) -> Result<(), DfKitError> {
+    if files.is_empty() {
+        return Err(DfKitError::CustomError(
+            "No files to concatenate".into(),
+        ));
+    }
+
     let mut dfs = vec![];
 
     for (i, file) in files.iter().enumerate() {
         let table_name = format!("t_{}", i);
-        let df = register_table(ctx, &table_name, file).await?;
+        // Overrides are checked against the first file
+        let df = if i == 0 {
+            register_table(ctx, &table_name, file).await?
+        } else {
+            register_secondary_table(ctx, &table_name, file).await?
+        };
         dfs.push(df);
     }
 
+    if union_by_name {
+        dfs = align_by_name(dfs)?;
+    }
+
+    if let Some(column) = &source_column {
+        dfs = dfs
+            .into_iter()
+            .zip(&files)
+            .map(|(df, file)| Ok(df.with_column(column, lit(file.display().to_string()))?))
+            .collect::<Result<_, DfKitError>>()?;
+    }
+
     let mut final_df = dfs.remove(0);
     for df in dfs {
         final_df = final_df.union(df)?;
     }
 
-    let format = file_type(&out_path)?;
+    let format = file_type(out_path)?;
     write_output(final_df, out_path, &format).await?;
     println!("Concatenated file written to: {}", out_path.display());
 
     Ok(())
 }
 
+/// Projects every frame onto the union of all column names, in first-seen
+/// order, widening each column to a type shared by all inputs and filling
+/// columns a file does not have with nulls.
+fn align_by_name(dfs: Vec<DataFrame>) -> Result<Vec<DataFrame>, DfKitError> {
+    let mut columns: Vec<(String, Vec<DataType>)> = vec![];
+    for df in &dfs {
+        for field in df.schema().fields() {
+            match columns.iter_mut().find(|(name, _)| name == field.name()) {
+                Some((_, types)) => types.push(field.data_type().clone()),
+                None => columns.push((field.name().clone(), vec![field.data_type().clone()])),
+            }
+        }
+    }
+
+    let unified: Vec<(String, DataType)> = columns
+        .into_iter()
+        .map(|(name, types)| {
+            let data_type = type_union_resolution(&types).unwrap_or(DataType::Utf8);
+            (name, data_type)
+        })
+        .collect();
+
+    let aligned = dfs
+        .into_iter()
+        .map(|df| {
+            let exprs: Vec<Expr> = unified
+                .iter()
+                .map(|(name, data_type)| {
+                    let expr = if df.schema().has_column_with_unqualified_name(name) {
+                        ident(name)
+                    } else {
+                        lit(ScalarValue::Null)
+                    };
+                    cast(expr, data_type.clone()).alias(name)
+                })
+                .collect();
+            Ok(df.select(exprs)?)
+        })
+        .collect::<Result<Vec<_>, DfKitError>>()?;
+
+    Ok(aligned)
+}
+
 pub async fn dedup(
     ctx: &SessionContext,
     filename: &Path,
+    on: &[String],
+    keep: &str,
+    order_by: &[String],
+    report: Option<PathBuf>,
     output: Option<PathBuf>,
 ) -> Result<(), DfKitError> {
-    let _ = register_table(&ctx, "t", &filename).await?;
-    let df = ctx.sql("SELECT DISTINCT * FROM t").await?;
+    let df = register_table(ctx, "t", filename).await?;
+    let columns: Vec<String> = df
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| quote_identifier(f.name()))
+        .collect();
+
+    // Number rows in file order so first and last mean what they say and
+    // break ties between rows with equal --order-by values
+    let mut fields = df.schema().as_arrow().fields().to_vec();
+    fields.push(Arc::new(Field::new("__dfkit_pos", DataType::UInt64, false)));
+    let schema: SchemaRef = Arc::new(Schema::new(fields));
+    let partition = PositionedPartition {
+        schema: Arc::clone(&schema),
+        df,
+    };
+    let provider = StreamingTable::try_new(schema, vec![Arc::new(partition)])?;
+    ctx.register_table("t_positioned", Arc::new(provider))?;
+
+    let partition_by = if on.is_empty() {
+        columns.join(", ")
+    } else {
+        on.iter()
+            .map(|c| quote_identifier(c))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let direction = match keep {
+        "first" | "none" => "ASC",
+        "last" => "DESC",
+        other => {
+            return Err(DfKitError::CustomError(format!(
+                "Unsupported keep strategy: {}",
+                other
+            )));
+        }
+    };
+    let order_clause = order_by
+        .iter()
+        .map(|c| format!("{} {} NULLS LAST, ", quote_identifier(c), direction))
+        .collect::<String>();
+
+    let kept = if keep == "none" {
+        "__dfkit_count = 1"
+    } else {
+        "__dfkit_row = 1"
+    };
+
+    let ranked = format!(
+        "SELECT *, \
+         ROW_NUMBER() OVER (PARTITION BY {0} ORDER BY {1}__dfkit_pos {2}) AS __dfkit_row, \
+         COUNT(*) OVER (PARTITION BY {0}) AS __dfkit_count \
+         FROM t_positioned",
+        partition_by, order_clause, direction
+    );
+    let select = |condition: &str| {
+        format!(
+            "SELECT {} FROM ({}) WHERE {} ORDER BY __dfkit_pos",
+            columns.join(", "),
+            ranked,
+            condition
+        )
+    };
+
+    if let Some(report_path) = report {
+        let removed = ctx.sql(&select(&format!("NOT ({})", kept))).await?;
+        let format = file_type(&report_path)?;
+        write_output(removed, &report_path, &format).await?;
+        println!("Removed duplicates written to: {}", report_path.display());
+    }
+
+    let df = ctx.sql(&select(kept)).await?;
 
     if let Some(out_path) = output {
-        let file_type = file_type(&filename)?;
+        let file_type = file_type(&out_path)?;
         write_output(df, &out_path, &file_type).await?;
         println!("Deduplicated file written to: {}", out_path.display());
     } else {
@@ -237,3 +853,1540 @@ pub async fn dedup(
 
     Ok(())
 }
+
+/// Streams a frame's partitions one after another, appending each row's
+/// zero-based position in the file as the last column.
+#[derive(Debug)]
+struct PositionedPartition {
+    schema: SchemaRef,
+    df: DataFrame,
+}
+
+impl PartitionStream for PositionedPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let df = self.df.clone();
+        let partitions = stream::once(async move { df.execute_stream_partitioned().await });
+        let batches = partitions.flat_map(|partitions| match partitions {
+            Ok(partitions) => stream::iter(partitions).flatten().boxed(),
+            Err(e) => stream::once(async { Err(e) }).boxed(),
+        });
+
+        let schema = Arc::clone(&self.schema);
+        let mut position = 0;
+        let positioned = batches.and_then(move |batch| {
+            let rows = batch.num_rows() as u64;
+            let mut columns = batch.columns().to_vec();
+            columns.push(Arc::new(UInt64Array::from_iter_values(
+                position..position + rows,
+            )));
+            position += rows;
+            future::ready(RecordBatch::try_new(Arc::clone(&schema), columns).map_err(Into::into))
+        });
+
+        Box::pin(RecordBatchStreamAdapter::new(
+            Arc::clone(&self.schema),
+            positioned,
+        ))
+    }
+}
+
+pub async fn pivot(
+    ctx: &SessionContext,
+    filename: &Path,
+    index: &[String],
+    columns: &str,
+    values: &str,
+    agg: &str,
+    output: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    let agg = match agg.to_lowercase().as_str() {
+        "sum" | "avg" | "min" | "max" | "count" | "median" => agg.to_lowercase(),
+        "mean" => "avg".to_string(),
+        other => {
+            return Err(DfKitError::CustomError(format!(
+                "Unsupported aggregation: {}",
+                other
+            )));
+        }
+    };
+
+    let _ = register_table(ctx, "t", filename).await?;
+
+    let pivot_col = quote_identifier(columns);
+    let distinct_sql = format!(
+        "SELECT DISTINCT CAST({0} AS VARCHAR) AS v FROM t WHERE {0} IS NOT NULL ORDER BY v",
+        pivot_col
+    );
+    let batches = ctx.sql(&distinct_sql).await?.collect().await?;
+
+    let mut pivot_values = vec![];
+    for batch in &batches {
+        let array = batch.column(0).as_string::<i32>();
+        pivot_values.extend(array.iter().flatten().map(|v| v.to_string()));
+    }
+
+    if pivot_values.is_empty() {
+        return Err(DfKitError::CustomError(format!(
+            "Column {} has no values to pivot on",
+            columns
+        )));
+    }
+
+    let index_cols = index
+        .iter()
+        .map(|c| quote_identifier(c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let pivot_exprs = pivot_values
+        .iter()
+        .map(|v| {
+            format!(
+                "{}(CASE WHEN CAST({} AS VARCHAR) = {} THEN {} END) AS {}",
+                agg,
+                pivot_col,
+                quote_literal(v),
+                quote_identifier(values),
+                quote_identifier(v)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let sql = if index.is_empty() {
+        format!("SELECT {} FROM t", pivot_exprs)
+    } else {
+        format!(
+            "SELECT {0}, {1} FROM t GROUP BY {0} ORDER BY {0}",
+            index_cols, pivot_exprs
+        )
+    };
+    let df = ctx.sql(&sql).await?;
+
+    if let Some(out_path) = output {
+        let format = file_type(&out_path)?;
+        write_output(df, &out_path, &format).await?;
+        println!("Pivoted file written to: {}", out_path.display());
+    } else {
+        df.show().await?;
+    }
+
+    Ok(())
+}
+
+pub async fn unpivot(
+    ctx: &SessionContext,
+    filename: &Path,
+    id_columns: &[String],
+    value_columns: &[String],
+    var_name: &str,
+    value_name: &str,
+    output: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    let df = register_table(ctx, "t", filename).await?;
+
+    let melt_columns: Vec<String> = df
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().to_string())
+        .filter(|name| !id_columns.contains(name))
+        .filter(|name| {
+            value_columns.is_empty() || value_columns.iter().any(|p| matches_pattern(p, name))
+        })
+        .collect();
+
+    if melt_columns.is_empty() {
+        return Err(DfKitError::CustomError(
+            "No value columns matched for unpivot".into(),
+        ));
+    }
+
+    let id_cols = id_columns
+        .iter()
+        .map(|c| format!("{}, ", quote_identifier(c)))
+        .collect::<String>();
+    let sql = melt_columns
+        .iter()
+        .map(|c| {
+            format!(
+                "SELECT {}{} AS {}, {} AS {} FROM t",
+                id_cols,
+                quote_literal(c),
+                quote_identifier(var_name),
+                quote_identifier(c),
+                quote_identifier(value_name)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ");
+    let df = ctx.sql(&sql).await?;
+
+    if let Some(out_path) = output {
+        let format = file_type(&out_path)?;
+        write_output(df, &out_path, &format).await?;
+        println!("Unpivoted file written to: {}", out_path.display());
+    } else {
+        df.show().await?;
+    }
+
+    Ok(())
+}
+
+pub async fn diff(
+    ctx: &SessionContext,
+    old_file: &Path,
+    new_file: &Path,
+    key: &[String],
+    output: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    if key.is_empty() {
+        return Err(DfKitError::CustomError(
+            "At least one key column is required".into(),
+        ));
+    }
+
+    let old_df = register_table(ctx, "t_old", old_file).await?;
+    let new_df = register_secondary_table(ctx, "t_new", new_file).await?;
+
+    for k in key {
+        if !old_df.schema().has_column_with_unqualified_name(k)
+            || !new_df.schema().has_column_with_unqualified_name(k)
+        {
+            return Err(DfKitError::CustomError(format!(
+                "Key column {} must exist in both files",
+                k
+            )));
+        }
+    }
+
+    let compared: Vec<String> = old_df
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().to_string())
+        .filter(|name| !key.contains(name))
+        .filter(|name| new_df.schema().has_column_with_unqualified_name(name))
+        .collect();
+
+    let skipped: Vec<String> = old_df
+        .schema()
+        .fields()
+        .iter()
+        .chain(new_df.schema().fields().iter())
+        .map(|f| f.name().to_string())
+        .filter(|name| !key.contains(name) && !compared.contains(name))
+        .collect();
+    if !skipped.is_empty() {
+        println!(
+            "Columns not present in both files were not compared: {}",
+            skipped.join(", ")
+        );
+    }
+
+    let key_exprs = key
+        .iter()
+        .map(|k| {
+            let k = quote_identifier(k);
+            format!("COALESCE(o.{0}, n.{0}) AS {0}", k)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let join_on = key
+        .iter()
+        .map(|k| format!("o.{0} = n.{0}", quote_identifier(k)))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    let changed = if compared.is_empty() {
+        "FALSE".to_string()
+    } else {
+        compared
+            .iter()
+            .map(|c| format!("(o.{0} IS DISTINCT FROM n.{0})", quote_identifier(c)))
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    };
+    let value_exprs = compared
+        .iter()
+        .map(|c| {
+            format!(
+                ", o.{} AS {}, n.{} AS {}",
+                quote_identifier(c),
+                quote_identifier(&format!("{}_before", c)),
+                quote_identifier(c),
+                quote_identifier(&format!("{}_after", c))
+            )
+        })
+        .collect::<String>();
+
+    // Marker columns record which side matched, since a key can itself be NULL
+    let compared_sql = format!(
+        "SELECT {key_exprs}, \
+         CASE WHEN o.__dfkit_old IS NULL THEN 'added' \
+         WHEN n.__dfkit_new IS NULL THEN 'removed' \
+         WHEN {changed} THEN 'changed' \
+         ELSE 'unchanged' END AS _change\
+         {value_exprs} \
+         FROM (SELECT *, TRUE AS __dfkit_old FROM t_old) o \
+         FULL OUTER JOIN (SELECT *, TRUE AS __dfkit_new FROM t_new) n ON {join_on}"
+    );
+
+    let summary = ctx
+        .sql(&format!(
+            "SELECT _change, COUNT(*) AS rows FROM ({}) GROUP BY _change ORDER BY _change",
+            compared_sql
+        ))
+        .await?;
+    summary.show().await?;
+
+    let order_by = key
+        .iter()
+        .map(|k| quote_identifier(k))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let df = ctx
+        .sql(&format!(
+            "SELECT * FROM ({}) WHERE _change <> 'unchanged' ORDER BY {}",
+            compared_sql, order_by
+        ))
+        .await?;
+
+    if let Some(out_path) = output {
+        let format = file_type(&out_path)?;
+        write_output(df, &out_path, &format).await?;
+        println!("Diff written to: {}", out_path.display());
+    } else {
+        df.show().await?;
+    }
+
+    Ok(())
+}
+
+struct ColumnInfo {
+    name: String,
+    data_type: String,
+    is_nullable: String,
+}
+
+async fn table_columns(
+    ctx: &SessionContext,
+    table_name: &str,
+) -> Result<Vec<ColumnInfo>, DfKitError> {
+    let sql = format!(
+        "SELECT column_name, data_type, is_nullable \
+         FROM information_schema.columns WHERE table_name = {} ORDER BY ordinal_position",
+        quote_literal(table_name)
+    );
+    let batches = ctx.sql(&sql).await?.collect().await?;
+
+    let mut columns = vec![];
+    for batch in &batches {
+        let names = batch.column(0).as_string::<i32>();
+        let data_types = batch.column(1).as_string::<i32>();
+        let nullables = batch.column(2).as_string::<i32>();
+        for row in 0..batch.num_rows() {
+            columns.push(ColumnInfo {
+                name: names.value(row).to_string(),
+                data_type: data_types.value(row).to_string(),
+                is_nullable: nullables.value(row).to_string(),
+            });
+        }
+    }
+
+    Ok(columns)
+}
+
+fn change_level(change: &str) -> &'static str {
+    match change {
+        "removed" | "type_changed" => "breaking",
+        "added" | "nullability_changed" => "warning",
+        _ => "info",
+    }
+}
+
+fn level_rank(level: &str) -> Option<u8> {
+    match level {
+        "info" => Some(1),
+        "warning" => Some(2),
+        "breaking" => Some(3),
+        _ => None,
+    }
+}
+
+pub async fn schema_diff(
+    ctx: &SessionContext,
+    left: &Path,
+    right: &Path,
+    fail_on: &str,
+) -> Result<(), DfKitError> {
+    let _ = register_table(ctx, "t_left", left).await?;
+    let _ = register_secondary_table(ctx, "t_right", right).await?;
+
+    let left_columns = table_columns(ctx, "t_left").await?;
+    let right_columns = table_columns(ctx, "t_right").await?;
+
+    // (column, change, before, after)
+    let mut changes: Vec<(String, &str, Option<String>, Option<String>)> = vec![];
+
+    for l in &left_columns {
+        match right_columns.iter().find(|r| r.name == l.name) {
+            None => changes.push((l.name.clone(), "removed", Some(l.data_type.clone()), None)),
+            Some(r) => {
+                if l.data_type != r.data_type {
+                    changes.push((
+                        l.name.clone(),
+                        "type_changed",
+                        Some(l.data_type.clone()),
+                        Some(r.data_type.clone()),
+                    ));
+                }
+                if l.is_nullable != r.is_nullable {
+                    changes.push((
+                        l.name.clone(),
+                        "nullability_changed",
+                        Some(l.is_nullable.clone()),
+                        Some(r.is_nullable.clone()),
+                    ));
+                }
+            }
+        }
+    }
+    for r in &right_columns {
+        if !left_columns.iter().any(|l| l.name == r.name) {
+            changes.push((r.name.clone(), "added", None, Some(r.data_type.clone())));
+        }
+    }
+
+    // Only the relative order of columns present in both files counts as reordering
+    let common_left: Vec<&str> = left_columns
+        .iter()
+        .map(|c| c.name.as_str())
+        .filter(|name| right_columns.iter().any(|r| r.name == *name))
+        .collect();
+    let common_right: Vec<&str> = right_columns
+        .iter()
+        .map(|c| c.name.as_str())
+        .filter(|name| left_columns.iter().any(|l| l.name == *name))
+        .collect();
+    for (position, name) in common_left.iter().enumerate() {
+        let new_position = common_right.iter().position(|r| r == name).unwrap();
+        if position != new_position {
+            changes.push((
+                name.to_string(),
+                "order_changed",
+                Some((position + 1).to_string()),
+                Some((new_position + 1).to_string()),
+            ));
+        }
+    }
+
+    if changes.is_empty() {
+        println!("Schemas are identical");
+        return Ok(());
+    }
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("column_name", DataType::Utf8, false),
+        Field::new("change", DataType::Utf8, false),
+        Field::new("before", DataType::Utf8, true),
+        Field::new("after", DataType::Utf8, true),
+        Field::new("level", DataType::Utf8, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from_iter_values(changes.iter().map(|c| &c.0))),
+            Arc::new(StringArray::from_iter_values(changes.iter().map(|c| c.1))),
+            Arc::new(StringArray::from_iter(changes.iter().map(|c| c.2.clone()))),
+            Arc::new(StringArray::from_iter(changes.iter().map(|c| c.3.clone()))),
+            Arc::new(StringArray::from_iter_values(
+                changes.iter().map(|c| change_level(c.1)),
+            )),
+        ],
+    )?;
+    ctx.read_batch(batch)?.show().await?;
+
+    if let Some(threshold) = level_rank(fail_on) {
+        let worst = changes
+            .iter()
+            .filter_map(|c| level_rank(change_level(c.1)))
+            .max()
+            .unwrap_or(0);
+        if worst >= threshold {
+            return Err(DfKitError::CustomError(format!(
+                "Schema differences at or above the '{}' level",
+                fail_on
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn validate(
+    ctx: &SessionContext,
+    filename: &Path,
+    rules_path: &Path,
+    failures: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    let rules = load_validation_rules(rules_path)?;
+    let _ = register_table(ctx, "t", filename).await?;
+
+    // (column, rule, query returning the rows that break the rule)
+    let mut checks: Vec<(String, String, String)> = vec![];
+
+    for (i, rule) in rules.columns.iter().enumerate() {
+        let c = quote_identifier(&rule.name);
+
+        if rule.not_null {
+            checks.push((
+                rule.name.clone(),
+                "not_null".into(),
+                format!("SELECT * FROM t WHERE {c} IS NULL"),
+            ));
+        }
+        if rule.unique {
+            checks.push((
+                rule.name.clone(),
+                "unique".into(),
+                format!(
+                    "SELECT * FROM t WHERE {c} IN \
+                     (SELECT {c} FROM t GROUP BY {c} HAVING COUNT(*) > 1)"
+                ),
+            ));
+        }
+        if let Some(range) = &rule.range {
+            let mut conditions = vec![];
+            let mut bounds = vec![];
+            if let Some(min) = &range.min {
+                conditions.push(format!("{c} < {}", min.to_sql()));
+                bounds.push(format!(">= {}", min.to_sql()));
+            }
+            if let Some(max) = &range.max {
+                conditions.push(format!("{c} > {}", max.to_sql()));
+                bounds.push(format!("<= {}", max.to_sql()));
+            }
+            if !conditions.is_empty() {
+                checks.push((
+                    rule.name.clone(),
+                    format!("range {}", bounds.join(" and ")),
+                    format!("SELECT * FROM t WHERE {}", conditions.join(" OR ")),
+                ));
+            }
+        }
+        if let Some(pattern) = &rule.regex {
+            checks.push((
+                rule.name.clone(),
+                format!("regex {}", pattern),
+                format!(
+                    "SELECT * FROM t WHERE {c} IS NOT NULL \
+                     AND NOT regexp_like(CAST({c} AS VARCHAR), {})",
+                    quote_literal(pattern)
+                ),
+            ));
+        }
+        if let Some(values) = &rule.allowed_values {
+            let values = values
+                .iter()
+                .map(|v| v.to_sql())
+                .collect::<Vec<_>>()
+                .join(", ");
+            checks.push((
+                rule.name.clone(),
+                format!("allowed_values ({})", values),
+                format!("SELECT * FROM t WHERE {c} IS NOT NULL AND {c} NOT IN ({values})"),
+            ));
+        }
+        if let Some(foreign_key) = &rule.foreign_key {
+            let table_name = format!("fk_{}", i);
+            let _ = register_secondary_table(ctx, &table_name, &foreign_key.file).await?;
+            checks.push((
+                rule.name.clone(),
+                format!(
+                    "foreign_key {}.{}",
+                    foreign_key.file.file_name().unwrap_or_default().to_string_lossy(),
+                    foreign_key.column
+                ),
+                format!(
+                    "SELECT * FROM t WHERE {c} IS NOT NULL AND {c} NOT IN \
+                     (SELECT {} FROM {table_name})",
+                    quote_identifier(&foreign_key.column)
+                ),
+            ));
+        }
+    }
+
+    // (column, rule, passed, failing rows)
+    let mut results: Vec<(String, String, bool, Option<u64>)> = vec![];
+    let mut failed_queries = vec![];
+
+    if let Some(row_count) = &rules.row_count {
+        let count = ctx.table("t").await?.count().await?;
+        let passed = row_count.min.is_none_or(|min| count >= min)
+            && row_count.max.is_none_or(|max| count <= max);
+        results.push(("*".into(), format!("row_count = {}", count), passed, None));
+    }
+
+    for (column, rule, sql) in checks {
+        let failing = ctx.sql(&sql).await?.count().await?;
+        if failing > 0 {
+            failed_queries.push(format!(
+                "SELECT *, {} AS _rule FROM ({})",
+                quote_literal(&format!("{}: {}", column, rule)),
+                sql
+            ));
+        }
+        results.push((column, rule, failing == 0, Some(failing as u64)));
+    }
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("column", DataType::Utf8, false),
+        Field::new("rule", DataType::Utf8, false),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("failing_rows", DataType::UInt64, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from_iter_values(results.iter().map(|r| &r.0))),
+            Arc::new(StringArray::from_iter_values(results.iter().map(|r| &r.1))),
+            Arc::new(StringArray::from_iter_values(
+                results.iter().map(|r| if r.2 { "PASS" } else { "FAIL" }),
+            )),
+            Arc::new(UInt64Array::from_iter(results.iter().map(|r| r.3))),
+        ],
+    )?;
+    ctx.read_batch(batch)?.show().await?;
+
+    if let Some(out_path) = failures.filter(|_| !failed_queries.is_empty()) {
+        let df = ctx.sql(&failed_queries.join(" UNION ALL ")).await?;
+        let format = file_type(&out_path)?;
+        write_output(df, &out_path, &format).await?;
+        println!("Failing rows written to: {}", out_path.display());
+    }
+
+    let failed = results.iter().filter(|r| !r.2).count();
+    if failed > 0 {
+        return Err(DfKitError::CustomError(format!(
+            "Validation failed: {} of {} rules did not pass",
+            failed,
+            results.len()
+        )));
+    }
+
+    Ok(())
+}
+
+pub async fn dfcast(
+    ctx: &SessionContext,
+    filename: &Path,
+    types: &str,
+    safe: bool,
+    datetime_format: Option<String>,
+    output: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    let targets = parse_type_overrides(types)?;
+    let df = register_table(ctx, "t", filename).await?;
+    let input_schema = df.schema().as_arrow().clone();
+    let schema = Arc::new(apply_schema_overrides(&input_schema, &targets)?);
+    let target_indices = targets
+        .iter()
+        .map(|f| input_schema.index_of(f.name()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let config = ctx.copied_config();
+    let mut writer = match &output {
+        Some(out_path) => Some(BatchWriter::try_new(
+            out_path,
+            &file_type(out_path)?,
+            &schema,
+            &config,
+        )?),
+        None => None,
+    };
+    let mut collected = vec![];
+    let mut nulled = vec![0; targets.len()];
+    let mut row_offset = 0;
+
+    for mut stream in df.execute_stream_partitioned().await? {
+        while let Some(batch) = stream.next().await {
+            let batch = batch?;
+            let mut columns = batch.columns().to_vec();
+
+            for (i, (target, &index)) in targets.iter().zip(&target_indices).enumerate() {
+                let source = batch.column(index);
+                let converted = cast_array(source, target.data_type(), datetime_format.as_deref())?;
+
+                // Values present before the cast but null after it could not be converted
+                let mut failed =
+                    (0..source.len()).filter(|&row| source.is_valid(row) && converted.is_null(row));
+                let first_failure = if safe { None } else { failed.next() };
+                if let Some(row) = first_failure {
+                    return Err(DfKitError::CustomError(format!(
+                        "Cannot cast value '{}' in column {} at row {} to {}",
+                        array_value_to_string(source, row)?,
+                        target.name(),
+                        row_offset + row + 1,
+                        target.data_type()
+                    )));
+                }
+                nulled[i] += failed.count();
+                columns[index] = converted;
+            }
+
+            let batch = RecordBatch::try_new(Arc::clone(&schema), columns)?;
+            row_offset += batch.num_rows();
+            match writer.as_mut() {
+                Some(writer) => writer.write(&batch)?,
+                None => collected.push(batch),
+            }
+        }
+    }
+
+    if safe {
+        let report_schema = Arc::new(Schema::new(vec![
+            Field::new("column", DataType::Utf8, false),
+            Field::new("data_type", DataType::Utf8, false),
+            Field::new("nulled", DataType::UInt64, false),
+        ]));
+        let report = RecordBatch::try_new(
+            report_schema,
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    targets.iter().map(|f| f.name()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    targets.iter().map(|f| f.data_type().to_string()),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    nulled.iter().map(|n| *n as u64),
+                )),
+            ],
+        )?;
+        ctx.read_batch(report)?.show().await?;
+    }
+
+    if let Some(writer) = writer {
+        let out_path = writer.finish()?;
+        println!("Cast file written to: {}", out_path.display());
+    } else {
+        let provider = MemTable::try_new(schema, vec![collected])?;
+        ctx.read_table(Arc::new(provider))?.show().await?;
+    }
+
+    Ok(())
+}
+
+/// Casts an array, turning values that cannot be converted into nulls. Strings
+/// cast to a timestamp or date are parsed with `datetime_format` when given.
+fn cast_array(
+    array: &ArrayRef,
+    to: &DataType,
+    datetime_format: Option<&str>,
+) -> Result<ArrayRef, DfKitError> {
+    let is_string = matches!(
+        array.data_type(),
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+    );
+
+    if let (Some(format), true) = (datetime_format, is_string) {
+        let strings = arrow_cast(array, &DataType::Utf8)?;
+        let parsed: Vec<Option<NaiveDateTime>> = strings
+            .as_string::<i32>()
+            .iter()
+            .map(|value| value.and_then(|v| parse_datetime(v, format)))
+            .collect();
+
+        match to {
+            DataType::Timestamp(unit, tz) => {
+                let values = parsed.into_iter().map(|dt| {
+                    dt.and_then(|dt| {
+                        let dt = dt.and_utc();
+                        match unit {
+                            TimeUnit::Second => Some(dt.timestamp()),
+                            TimeUnit::Millisecond => Some(dt.timestamp_millis()),
+                            TimeUnit::Microsecond => Some(dt.timestamp_micros()),
+                            TimeUnit::Nanosecond => dt.timestamp_nanos_opt(),
+                        }
+                    })
+                });
+                let array: ArrayRef = match unit {
+                    TimeUnit::Second => Arc::new(
+                        TimestampSecondArray::from_iter(values).with_timezone_opt(tz.clone()),
+                    ),
+                    TimeUnit::Millisecond => Arc::new(
+                        TimestampMillisecondArray::from_iter(values).with_timezone_opt(tz.clone()),
+                    ),
+                    TimeUnit::Microsecond => Arc::new(
+                        TimestampMicrosecondArray::from_iter(values).with_timezone_opt(tz.clone()),
+                    ),
+                    TimeUnit::Nanosecond => Arc::new(
+                        TimestampNanosecondArray::from_iter(values).with_timezone_opt(tz.clone()),
+                    ),
+                };
+                return Ok(array);
+            }
+            DataType::Date32 => {
+                let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+                let values = parsed
+                    .into_iter()
+                    .map(|dt| dt.map(|dt| (dt.date() - epoch).num_days() as i32));
+                return Ok(Arc::new(Date32Array::from_iter(values)));
+            }
+            _ => {}
+        }
+    }
+
+    let options = CastOptions {
+        safe: true,
+        ..Default::default()
+    };
+    Ok(cast_with_options(array, to, &options)?)
+}
+
+fn parse_datetime(value: &str, format: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, format).ok().or_else(|| {
+        NaiveDate::parse_from_str(value, format)
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+}
+
+pub async fn mutate(
+    ctx: &SessionContext,
+    filename: &Path,
+    assignments: &[String],
+    output: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    let mut df = register_table(ctx, "t", filename).await?;
+
+    // Each assignment sees the columns added by the ones before it
+    for assignment in assignments {
+        let (name, sql) = assignment.split_once('=').ok_or_else(|| {
+            DfKitError::CustomError(format!("Expected name=expression, got: {}", assignment))
+        })?;
+        let expr = df.parse_sql_expr(sql.trim())?;
+        df = df.with_column(name.trim(), expr)?;
+    }
+
+    if let Some(out_path) = output {
+        let format = file_type(&out_path)?;
+        write_output(df, &out_path, &format).await?;
+        println!("Mutated file written to: {}", out_path.display());
+    } else {
+        df.show().await?;
+    }
+
+    Ok(())
+}
+
+pub async fn fillna(
+    ctx: &SessionContext,
+    filename: &Path,
+    columns: &[String],
+    strategy: FillStrategy,
+    order_by: &[String],
+    partition_by: &[String],
+    output: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    let df = register_table(ctx, "t", filename).await?;
+
+    for column in columns {
+        if !df.schema().has_column_with_unqualified_name(column) {
+            return Err(DfKitError::CustomError(format!(
+                "Column {} not found",
+                column
+            )));
+        }
+    }
+
+    let is_ordered = matches!(strategy, FillStrategy::Forward | FillStrategy::Backward);
+    if is_ordered && order_by.is_empty() {
+        return Err(DfKitError::CustomError(
+            "Forward and backward fill require --order-by".into(),
+        ));
+    }
+
+    let qualified = |names: &[String]| {
+        names
+            .iter()
+            .map(|c| format!("t.{}", quote_identifier(c)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let window_partition = if partition_by.is_empty() {
+        String::new()
+    } else {
+        format!("PARTITION BY {} ", qualified(partition_by))
+    };
+    let order_list = qualified(order_by);
+    let keys = partition_by
+        .iter()
+        .map(|c| format!("{}, ", quote_identifier(c)))
+        .collect::<String>();
+    let group_by = if partition_by.is_empty() {
+        String::new()
+    } else {
+        format!(" GROUP BY {}", keys.trim_end_matches(", "))
+    };
+
+    let mut select = vec![];
+    let mut joins = vec![];
+    for field in df.schema().fields() {
+        let name = field.name();
+        let data_type = field.data_type();
+        let c = quote_identifier(name);
+
+        // Without --columns, fill every column the strategy applies to
+        let target = if columns.is_empty() {
+            let numeric_only = matches!(strategy, FillStrategy::Mean | FillStrategy::Median);
+            !partition_by.contains(name)
+                && !order_by.contains(name)
+                && (!numeric_only || data_type.is_numeric())
+        } else {
+            columns.contains(name)
+        };
+        if !target {
+            select.push(format!("t.{}", c));
+            continue;
+        }
+
+        // Aggregate strategies compute one fill value per partition and join it back
+        let stats = match &strategy {
+            FillStrategy::Mean => Some(format!(
+                "SELECT {keys}AVG({c}) AS __dfkit_fill FROM t{group_by}"
+            )),
+            FillStrategy::Median if data_type.is_integer() => Some(format!(
+                "SELECT {keys}MEDIAN(CAST({c} AS DOUBLE)) AS __dfkit_fill FROM t{group_by}"
+            )),
+            FillStrategy::Median => Some(format!(
+                "SELECT {keys}MEDIAN({c}) AS __dfkit_fill FROM t{group_by}"
+            )),
+            FillStrategy::Mode => Some(format!(
+                "SELECT {keys}__dfkit_fill FROM (\
+                 SELECT {keys}{c} AS __dfkit_fill, ROW_NUMBER() OVER \
+                 ({partition}ORDER BY COUNT(*) DESC, {c}) AS __dfkit_rank \
+                 FROM t WHERE {c} IS NOT NULL GROUP BY {keys}{c}) \
+                 WHERE __dfkit_rank = 1",
+                partition = if partition_by.is_empty() {
+                    String::new()
+                } else {
+                    format!("PARTITION BY {} ", keys.trim_end_matches(", "))
+                }
+            )),
+            _ => None,
+        };
+
+        let fill = match &strategy {
+            FillStrategy::Value(value) => {
+                format!("arrow_cast({}, '{}')", quote_literal(value), data_type)
+            }
+            FillStrategy::Forward => format!(
+                "LAST_VALUE(t.{c}) IGNORE NULLS OVER ({window_partition}ORDER BY {order_list} \
+                 ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW)"
+            ),
+            FillStrategy::Backward => format!(
+                "FIRST_VALUE(t.{c}) IGNORE NULLS OVER ({window_partition}ORDER BY {order_list} \
+                 ROWS BETWEEN CURRENT ROW AND UNBOUNDED FOLLOWING)"
+            ),
+            _ => {
+                let alias = format!("s_{}", joins.len());
+                let stats = stats.unwrap_or_default();
+                if partition_by.is_empty() {
+                    joins.push(format!(" CROSS JOIN ({}) {}", stats, alias));
+                } else {
+                    let on = partition_by
+                        .iter()
+                        .map(|p| {
+                            let p = quote_identifier(p);
+                            format!("t.{p} IS NOT DISTINCT FROM {alias}.{p}")
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" AND ");
+                    joins.push(format!(" LEFT JOIN ({}) {} ON {}", stats, alias, on));
+                }
+                format!("{}.__dfkit_fill", alias)
+            }
+        };
+
+        // A mean or median of integers is widened rather than truncated
+        let data_type = match &strategy {
+            FillStrategy::Mean | FillStrategy::Median if data_type.is_integer() => {
+                &DataType::Float64
+            }
+            _ => data_type,
+        };
+        select.push(format!(
+            "arrow_cast(COALESCE(t.{c}, {fill}), '{data_type}') AS {c}"
+        ));
+    }
+
+    let mut sql = format!("SELECT {} FROM t{}", select.join(", "), joins.concat());
+    if is_ordered {
+        let ordering = partition_by
+            .iter()
+            .chain(order_by)
+            .map(|c| quote_identifier(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        sql = format!("SELECT * FROM ({}) ORDER BY {}", sql, ordering);
+    }
+    let df = ctx.sql(&sql).await?;
+
+    if let Some(out_path) = output {
+        let format = file_type(&out_path)?;
+        write_output(df, &out_path, &format).await?;
+        println!("Filled file written to: {}", out_path.display());
+    } else {
+        df.show().await?;
+    }
+
+    Ok(())
+}
+
+pub async fn replace(
+    ctx: &SessionContext,
+    filename: &Path,
+    column: &str,
+    mapping: &str,
+    output: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    let df = register_table(ctx, "t", filename).await?;
+    if !df.schema().has_column_with_unqualified_name(column) {
+        return Err(DfKitError::CustomError(format!(
+            "Column {} not found",
+            column
+        )));
+    }
+
+    let mut cases = String::new();
+    for pair in mapping.split(',') {
+        let (from, to) = pair
+            .split_once(':')
+            .ok_or_else(|| DfKitError::CustomError(format!("Expected old:new, got: {}", pair)))?;
+        cases.push_str(&format!(
+            "WHEN {} THEN {} ",
+            quote_literal(from.trim()),
+            quote_literal(to.trim())
+        ));
+    }
+
+    let select = df
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| {
+            let c = quote_identifier(f.name());
+            if f.name() == column {
+                format!(
+                    "arrow_cast(CASE CAST({c} AS VARCHAR) {cases}ELSE CAST({c} AS VARCHAR) END, \
+                     '{}') AS {c}",
+                    f.data_type()
+                )
+            } else {
+                c
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let df = ctx.sql(&format!("SELECT {} FROM t", select)).await?;
+
+    if let Some(out_path) = output {
+        let format = file_type(&out_path)?;
+        write_output(df, &out_path, &format).await?;
+        println!("Replaced file written to: {}", out_path.display());
+    } else {
+        df.show().await?;
+    }
+
+    Ok(())
+}
+
+pub async fn rename(
+    ctx: &SessionContext,
+    filename: &Path,
+    mapping: Option<String>,
+    normalize: Option<NameStyle>,
+    output: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    let mut df = register_table(ctx, "t", filename).await?;
+
+    if let Some(mapping) = mapping {
+        let mut renames = HashMap::new();
+        for pair in mapping.split(',') {
+            let (from, to) = pair.split_once(':').ok_or_else(|| {
+                DfKitError::CustomError(format!("Expected old:new, got: {}", pair))
+            })?;
+            let (from, to) = (from.trim(), to.trim());
+            if !df.schema().has_column_with_unqualified_name(from) {
+                return Err(DfKitError::CustomError(format!(
+                    "Column {} not found",
+                    from
+                )));
+            }
+            renames.insert(from, to);
+        }
+        let exprs = df
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| {
+                let name = f.name().as_str();
+                ident(name).alias(*renames.get(name).unwrap_or(&name))
+            })
+            .collect::<Vec<_>>();
+        df = df.select(exprs)?;
+    }
+
+    if let Some(style) = normalize {
+        df = normalize_columns(df, style)?;
+    }
+
+    if let Some(out_path) = output {
+        let format = file_type(&out_path)?;
+        write_output(df, &out_path, &format).await?;
+        println!("Renamed file written to: {}", out_path.display());
+    } else {
+        df.show().await?;
+    }
+
+    Ok(())
+}
+
+pub async fn top(
+    ctx: &SessionContext,
+    filename: &Path,
+    n: usize,
+    by: &str,
+    group_by: &[String],
+    ascending: bool,
+    output: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    let df = register_table(ctx, "t", filename).await?;
+
+    let top_df = if group_by.is_empty() {
+        // Sort followed by a limit is planned as a TopK, which keeps only N rows in memory
+        df.sort(vec![ident(by).sort(ascending, false)])?
+            .limit(0, Some(n))?
+    } else {
+        let columns = df
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| quote_identifier(f.name()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let partition_by = group_by
+            .iter()
+            .map(|c| quote_identifier(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let direction = if ascending { "ASC" } else { "DESC" };
+        ctx.sql(&format!(
+            "SELECT {columns} FROM (\
+             SELECT *, ROW_NUMBER() OVER (PARTITION BY {partition_by} \
+             ORDER BY {by} {direction} NULLS LAST) AS __dfkit_rank FROM t) \
+             WHERE __dfkit_rank <= {n} \
+             ORDER BY {partition_by}, __dfkit_rank",
+            by = quote_identifier(by)
+        ))
+        .await?
+    };
+
+    if let Some(out_path) = output {
+        let format = file_type(&out_path)?;
+        write_output(top_df, &out_path, &format).await?;
+        println!("Top rows written to: {}", out_path.display());
+    } else {
+        top_df.show().await?;
+    }
+
+    Ok(())
+}
+
+pub async fn parquet_meta(
+    ctx: &SessionContext,
+    filename: &Path,
+    output_format: &str,
+) -> Result<(), DfKitError> {
+    // The footer is read straight from disk, so HTTP sources are downloaded first
+    let meta = match storage_type(filename)? {
+        StorageType::Local => read_parquet_metadata(filename)?,
+        StorageType::Url => {
+            let url = filename
+                .to_str()
+                .ok_or(DfKitError::FileParse(FileParseError::InvalidExtension))?;
+            let (_tempfile, local_path) = download_to_tempfile(url).await?;
+            read_parquet_metadata(&local_path)?
+        }
+        StorageType::S3 | StorageType::GCS => {
+            return Err(DfKitError::CustomError(
+                "parquet-meta needs a local or HTTP Parquet file".into(),
+            ));
+        }
+    };
+
+    if output_format == "json" {
+        println!("{}", serde_json::to_string_pretty(&meta)?);
+        return Ok(());
+    }
+
+    // Long values such as the embedded Arrow schema are cut short in table output
+    let shorten = |value: &str| {
+        if value.chars().count() > 64 {
+            format!("{}...", value.chars().take(61).collect::<String>())
+        } else {
+            value.to_string()
+        }
+    };
+    let mut properties = vec![
+        ("format_version".to_string(), Some(meta.version.to_string())),
+        ("created_by".to_string(), meta.created_by.clone()),
+        ("num_rows".to_string(), Some(meta.num_rows.to_string())),
+        (
+            "num_row_groups".to_string(),
+            Some(meta.row_groups.len().to_string()),
+        ),
+    ];
+    for (key, value) in &meta.key_value_metadata {
+        properties.push((format!("metadata.{}", key), value.as_deref().map(shorten)));
+    }
+    let file_batch = RecordBatch::try_from_iter(vec![
+        (
+            "property",
+            Arc::new(StringArray::from_iter_values(
+                properties.iter().map(|p| &p.0),
+            )) as ArrayRef,
+        ),
+        (
+            "value",
+            Arc::new(StringArray::from_iter(
+                properties.iter().map(|p| p.1.clone()),
+            )),
+        ),
+    ])?;
+    ctx.read_batch(file_batch)?.show().await?;
+
+    let row_groups = &meta.row_groups;
+    let row_group_batch = RecordBatch::try_from_iter(vec![
+        (
+            "row_group",
+            Arc::new(UInt64Array::from_iter_values(
+                row_groups.iter().map(|rg| rg.index as u64),
+            )) as ArrayRef,
+        ),
+        (
+            "num_rows",
+            Arc::new(Int64Array::from_iter_values(
+                row_groups.iter().map(|rg| rg.num_rows),
+            )),
+        ),
+        (
+            "total_byte_size",
+            Arc::new(Int64Array::from_iter_values(
+                row_groups.iter().map(|rg| rg.total_byte_size),
+            )),
+        ),
+        (
+            "compressed_size",
+            Arc::new(Int64Array::from_iter_values(
+                row_groups.iter().map(|rg| rg.compressed_size),
+            )),
+        ),
+    ])?;
+    ctx.read_batch(row_group_batch)?.show().await?;
+
+    let chunks = row_groups
+        .iter()
+        .flat_map(|rg| rg.columns.iter().map(move |chunk| (rg.index as u64, chunk)))
+        .collect::<Vec<_>>();
+    let chunk_batch = RecordBatch::try_from_iter(vec![
+        (
+            "row_group",
+            Arc::new(UInt64Array::from_iter_values(chunks.iter().map(|c| c.0))) as ArrayRef,
+        ),
+        (
+            "column",
+            Arc::new(StringArray::from_iter_values(
+                chunks.iter().map(|c| &c.1.column),
+            )),
+        ),
+        (
+            "type",
+            Arc::new(StringArray::from_iter_values(
+                chunks.iter().map(|c| &c.1.physical_type),
+            )),
+        ),
+        (
+            "compression",
+            Arc::new(StringArray::from_iter_values(
+                chunks.iter().map(|c| &c.1.compression),
+            )),
+        ),
+        (
+            "encodings",
+            Arc::new(StringArray::from_iter_values(
+                chunks.iter().map(|c| c.1.encodings.join(",")),
+            )),
+        ),
+        (
+            "compressed_size",
+            Arc::new(Int64Array::from_iter_values(
+                chunks.iter().map(|c| c.1.compressed_size),
+            )),
+        ),
+        (
+            "uncompressed_size",
+            Arc::new(Int64Array::from_iter_values(
+                chunks.iter().map(|c| c.1.uncompressed_size),
+            )),
+        ),
+        (
+            "min",
+            Arc::new(StringArray::from_iter(
+                chunks.iter().map(|c| c.1.min.as_deref().map(shorten)),
+            )),
+        ),
+        (
+            "max",
+            Arc::new(StringArray::from_iter(
+                chunks.iter().map(|c| c.1.max.as_deref().map(shorten)),
+            )),
+        ),
+        (
+            "null_count",
+            Arc::new(UInt64Array::from_iter(
+                chunks.iter().map(|c| c.1.null_count),
+            )),
+        ),
+        (
+            "page_index",
+            Arc::new(BooleanArray::from_iter(
+                chunks.iter().map(|c| Some(c.1.page_index)),
+            )),
+        ),
+        (
+            "bloom_filter",
+            Arc::new(BooleanArray::from_iter(
+                chunks.iter().map(|c| Some(c.1.bloom_filter)),
+            )),
+        ),
+    ])?;
+    ctx.read_batch(chunk_batch)?.show().await?;
+
+    Ok(())
+}
+
+pub async fn flatten(
+    ctx: &SessionContext,
+    filename: &Path,
+    separator: &str,
+    max_depth: Option<usize>,
+    explode: &[String],
+    output: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    let df = register_table(ctx, "t", filename).await?;
+    let mut df = flatten_structs(df, separator, max_depth)?;
+
+    if !explode.is_empty() {
+        for column in explode {
+            let (_, field) = df
+                .schema()
+                .qualified_field_with_unqualified_name(column)
+                .map_err(|_| DfKitError::CustomError(format!("Column {} not found", column)))?;
+            if !matches!(
+                field.data_type(),
+                DataType::List(_) | DataType::LargeList(_) | DataType::FixedSizeList(_, _)
+            ) {
+                return Err(DfKitError::CustomError(format!(
+                    "Column {} is not a list and cannot be exploded",
+                    column
+                )));
+            }
+        }
+        // unnest drops rows whose list is empty, so those become null lists first,
+        // which are kept as a single row with a null value
+        let exprs = df
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| {
+                let column = ident(f.name());
+                if !explode.contains(f.name()) {
+                    return Ok(column);
+                }
+                let null = lit(ScalarValue::try_from(f.data_type())?);
+                Ok(when(array_length(column.clone()).eq(lit(0u64)), null)
+                    .otherwise(column)?
+                    .alias(f.name()))
+            })
+            .collect::<Result<Vec<_>, DfKitError>>()?;
+        let (state, plan) = df.select(exprs)?.into_parts();
+        let plan = LogicalPlanBuilder::from(plan)
+            .unnest_columns_with_options(
+                explode.iter().map(Column::from_name).collect(),
+                UnnestOptions::new().with_preserve_nulls(true),
+            )?
+            .build()?;
+        // Exploding a list of structs yields struct columns, flatten those as well
+        df = flatten_structs(DataFrame::new(state, plan), separator, max_depth)?;
+    }
+
+    if let Some(out_path) = output {
+        let format = file_type(&out_path)?;
+        write_output(df, &out_path, &format).await?;
+        println!("Flattened file written to: {}", out_path.display());
+    } else {
+        df.show().await?;
+    }
+
+    Ok(())
+}
+
+/// Expands struct columns into `parent{separator}child` columns, one nesting level per pass.
+fn flatten_structs(
+    mut df: DataFrame,
+    separator: &str,
+    max_depth: Option<usize>,
+) -> Result<DataFrame, DfKitError> {
+    let mut depth = 0;
+    while max_depth.is_none_or(|max| depth < max) {
+        let mut structs = Vec::new();
+        let mut renames = HashMap::new();
+        for field in df.schema().fields() {
+            if let DataType::Struct(children) = field.data_type() {
+                structs.push(Column::from_name(field.name()));
+                for child in children {
+                    // unnest names struct fields `parent.child`
+                    renames.insert(
+                        format!("{}.{}", field.name(), child.name()),
+                        format!("{}{}{}", field.name(), separator, child.name()),
+                    );
+                }
+            }
+        }
+        if structs.is_empty() {
+            break;
+        }
+
+        let (state, plan) = df.into_parts();
+        let plan = LogicalPlanBuilder::from(plan)
+            .unnest_columns_with_options(structs, UnnestOptions::new())?
+            .build()?;
+        df = DataFrame::new(state, plan);
+
+        let exprs = df
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| ident(f.name()).alias(renames.get(f.name()).unwrap_or(f.name())))
+            .collect::<Vec<_>>();
+        df = df.select(exprs)?;
+        depth += 1;
+    }
+    Ok(df)
+}
+
+enum NestNode {
+    Column(String),
+    Struct(Vec<(String, NestNode)>),
+}
+
+impl NestNode {
+    fn insert(
+        children: &mut Vec<(String, NestNode)>,
+        path: &[&str],
+        column: &str,
+    ) -> Result<(), DfKitError> {
+        let conflict = || {
+            DfKitError::CustomError(format!(
+                "Column {} conflicts with another column of the same nested name",
+                column
+            ))
+        };
+        let (key, rest) = path.split_first().ok_or_else(conflict)?;
+        let existing = children.iter_mut().find(|(name, _)| name == key);
+        match (existing, rest.is_empty()) {
+            (None, true) => children.push((key.to_string(), NestNode::Column(column.to_string()))),
+            (None, false) => {
+                let mut nested = Vec::new();
+                NestNode::insert(&mut nested, rest, column)?;
+                children.push((key.to_string(), NestNode::Struct(nested)));
+            }
+            (Some((_, NestNode::Struct(nested))), false) => NestNode::insert(nested, rest, column)?,
+            (Some(_), _) => return Err(conflict()),
+        }
+        Ok(())
+    }
+
+    fn into_expr(self) -> Expr {
+        match self {
+            NestNode::Column(name) => ident(name),
+            NestNode::Struct(children) => named_struct(
+                children
+                    .into_iter()
+                    .flat_map(|(name, node)| [lit(name), node.into_expr()])
+                    .collect(),
+            ),
+        }
+    }
+}
+
+pub async fn nest(
+    ctx: &SessionContext,
+    filename: &Path,
+    separator: &str,
+    columns: &[String],
+    into: Option<String>,
+    output: Option<PathBuf>,
+) -> Result<(), DfKitError> {
+    let df = register_table(ctx, "t", filename).await?;
+
+    for column in columns {
+        if !df.schema().has_column_with_unqualified_name(column) {
+            return Err(DfKitError::CustomError(format!(
+                "Column {} not found",
+                column
+            )));
+        }
+    }
+
+    let mut nodes = Vec::new();
+    for field in df.schema().fields() {
+        let name = field.name();
+        match &into {
+            // Explicit columns go into one struct placed where the first of them was
+            Some(into) if columns.contains(name) => {
+                let child = name
+                    .strip_prefix(&format!("{}{}", into, separator))
+                    .unwrap_or(name);
+                NestNode::insert(&mut nodes, &[into.as_str(), child], name)?;
+            }
+            Some(_) => NestNode::insert(&mut nodes, &[name.as_str()], name)?,
+            None => {
+                let path = name.split(separator).collect::<Vec<_>>();
+                NestNode::insert(&mut nodes, &path, name)?;
+            }
+        }
+    }
+
+    let exprs = nodes
+        .into_iter()
+        .map(|(name, node)| node.into_expr().alias(name))
+        .collect::<Vec<_>>();
+    let df = df.select(exprs)?;
+
+    if let Some(out_path) = output {
+        let format = file_type(&out_path)?;
+        write_output(df, &out_path, &format).await?;
+        println!("Nested file written to: {}", out_path.display());
+    } else {
+        df.show().await?;
+    }
+
+    Ok(())
+}
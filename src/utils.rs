@@ -1,8 +1,49 @@
+use calamine::{Data as ExcelData, DataType as _, Reader as _, open_workbook_auto};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use datafusion::arrow::array::{
+    Array, ArrayRef, AsArray, BinaryArray, BooleanArray, Date32Array, Float64Array, Int64Array,
+    RecordBatch, RecordBatchOptions, StringArray, StringBuilder, TimestampMillisecondArray,
+};
+use datafusion::arrow::compute::{CastOptions, cast as arrow_cast, cast_with_options};
+use datafusion::arrow::csv::{Writer as CsvWriter, WriterBuilder as CsvWriterBuilder};
+use datafusion::arrow::datatypes::{
+    DataType, Date32Type, Field, Float64Type, Int64Type, Schema, SchemaRef, TimeUnit,
+    TimestampMillisecondType, UInt64Type,
+};
 use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::json::reader::infer_json_schema_from_iterator;
+use datafusion::arrow::json::{
+    ArrayWriter, LineDelimitedWriter, ReaderBuilder as JsonReaderBuilder,
+};
+use datafusion::arrow::util::display::array_value_to_string;
+use datafusion::catalog::streaming::StreamingTable;
+use datafusion::config::TableParquetOptions;
 use datafusion::dataframe::DataFrameWriteOptions;
+use datafusion::datasource::MemTable;
 use datafusion::error::DataFusionError;
+use datafusion::execution::disk_manager::DiskManagerConfig;
+use datafusion::execution::memory_pool::FairSpillPool;
+use datafusion::execution::runtime_env::{RuntimeEnv, RuntimeEnvBuilder};
+use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+use datafusion::logical_expr::{cast, ident};
+use datafusion::parquet::arrow::ArrowWriter;
+use datafusion::parquet::errors::ParquetError;
+use datafusion::parquet::file::properties::{WriterProperties, WriterPropertiesBuilder};
+use datafusion::parquet::file::reader::{FileReader, SerializedFileReader};
+use datafusion::parquet::file::statistics::Statistics;
+use datafusion::physical_plan::stream::RecordBatchReceiverStreamBuilder;
+use datafusion::physical_plan::streaming::PartitionStream;
 use datafusion::prelude::*;
+use futures::StreamExt;
 use reqwest::Client;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OpenFlags, params_from_iter};
+use rust_xlsxwriter::{Format as XlsxFormat, Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tempfile::NamedTempFile;
@@ -17,6 +58,23 @@ pub enum FileFormat {
     Parquet,
     Json,
     Avro,
+    Excel,
+    Sqlite,
+    FixedWidth,
+}
+
+impl FileFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FileFormat::Csv => "csv",
+            FileFormat::Parquet => "parquet",
+            FileFormat::Json => "json",
+            FileFormat::Avro => "avro",
+            FileFormat::Excel => "xlsx",
+            FileFormat::Sqlite => "sqlite",
+            FileFormat::FixedWidth => "fwf",
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -27,6 +85,196 @@ pub enum StorageType {
     GCS
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum SplitMode {
+    Chunks(usize),
+    Rows(usize),
+    MaxSize(usize),
+    By(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameStyle {
+    SnakeCase,
+    Lower,
+    Upper,
+}
+
+/// One `--columns` entry of `sort`, e.g. `revenue desc nulls last`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortSpec {
+    pub column: String,
+    pub ascending: bool,
+    pub nulls_first: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FillStrategy {
+    Value(String),
+    Forward,
+    Backward,
+    Mean,
+    Median,
+    Mode,
+}
+
+/// Options applied by `register_table` when reading files, carried on the
+/// session config as an extension so every command picks them up.
+#[derive(Debug, Default)]
+pub struct ReadOptions {
+    pub schema_overrides: Vec<Field>,
+    pub normalize_names: Option<NameStyle>,
+    /// Dotted path to the records inside each JSON document, e.g. `data.items`.
+    pub json_path: Option<String>,
+    /// Spreadsheet sheet to read, by name or zero-based index.
+    pub sheet: Option<String>,
+    /// A1-style cell range to read from the sheet, e.g. `B3:F200`.
+    pub range: Option<String>,
+    /// SQLite table to read when the source doesn't name one.
+    pub table: Option<String>,
+    /// Column positions for fixed-width files, from `--layout`.
+    pub layout: Option<FixedWidthLayout>,
+}
+
+/// Options applied by `write_output` and `BatchWriter` when writing files,
+/// carried on the session config alongside `ReadOptions`.
+#[derive(Debug, Default)]
+pub struct WriteOptions {
+    pub parquet: TableParquetOptions,
+    /// Write JSON as a single array rather than newline-delimited records.
+    pub json_array: bool,
+    /// SQLite table to create or append to when the output doesn't name one.
+    pub table: Option<String>,
+}
+
+/// On-disk schema format used by `--schema` and `schema --export`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SchemaFile {
+    pub fields: Vec<SchemaField>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SchemaField {
+    pub name: String,
+    pub data_type: String,
+    #[serde(default = "default_nullable")]
+    pub nullable: bool,
+}
+
+fn default_nullable() -> bool {
+    true
+}
+
+/// Column layout for fixed-width files, read from `--layout`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixedWidthLayout {
+    pub columns: Vec<FixedWidthColumn>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixedWidthColumn {
+    pub name: String,
+    /// One-based character position where the column begins.
+    pub start: usize,
+    pub length: usize,
+    #[serde(rename = "type", default = "default_fixed_width_type")]
+    pub data_type: String,
+}
+
+fn default_fixed_width_type() -> String {
+    "Utf8".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum RuleValue {
+    Number(f64),
+    Text(String),
+}
+
+impl RuleValue {
+    pub fn to_sql(&self) -> String {
+        match self {
+            RuleValue::Number(n) => n.to_string(),
+            RuleValue::Text(s) => quote_literal(s),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RangeRule {
+    pub min: Option<RuleValue>,
+    pub max: Option<RuleValue>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForeignKeyRule {
+    pub file: PathBuf,
+    pub column: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RowCountRule {
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ColumnRules {
+    pub name: String,
+    #[serde(default)]
+    pub not_null: bool,
+    #[serde(default)]
+    pub unique: bool,
+    pub range: Option<RangeRule>,
+    pub regex: Option<String>,
+    pub allowed_values: Option<Vec<RuleValue>>,
+    pub foreign_key: Option<ForeignKeyRule>,
+}
+
+/// Expectations loaded from a `validate --rules` YAML file.
+#[derive(Debug, Deserialize)]
+pub struct ValidationRules {
+    pub row_count: Option<RowCountRule>,
+    #[serde(default)]
+    pub columns: Vec<ColumnRules>,
+}
+
+/// Column chunk details reported by `parquet-meta`.
+#[derive(Debug, Serialize)]
+pub struct ParquetColumnChunkMeta {
+    pub column: String,
+    pub physical_type: String,
+    pub compression: String,
+    pub encodings: Vec<String>,
+    pub compressed_size: i64,
+    pub uncompressed_size: i64,
+    pub min: Option<String>,
+    pub max: Option<String>,
+    pub null_count: Option<u64>,
+    pub page_index: bool,
+    pub bloom_filter: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParquetRowGroupMeta {
+    pub index: usize,
+    pub num_rows: i64,
+    pub total_byte_size: i64,
+    pub compressed_size: i64,
+    pub columns: Vec<ParquetColumnChunkMeta>,
+}
+
+/// File footer summary reported by `parquet-meta`.
+#[derive(Debug, Serialize)]
+pub struct ParquetFileMeta {
+    pub version: i32,
+    pub created_by: Option<String>,
+    pub num_rows: i64,
+    pub key_value_metadata: BTreeMap<String, Option<String>>,
+    pub row_groups: Vec<ParquetRowGroupMeta>,
+}
+
 #[derive(Error, Debug)]
 pub enum FileParseError {
     #[error("unsupported file format")]
@@ -47,7 +295,7 @@ pub enum DfKitError {
     FileParse(#[from] FileParseError),
 
     #[error("DataFusion error: {0}")]
-    DataFusion(#[from] DataFusionError),
+    DataFusion(#[source] Box<DataFusionError>),
 
     #[error("Unknown error")]
     Unknown,
@@ -72,22 +320,163 @@ pub enum DfKitError {
 
     #[error("ObjectStore error: {0}")]
     ObjectStore(#[from] object_store::Error),
+
+    #[error("Parquet error: {0}")]
+    Parquet(#[from] ParquetError),
+
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Avro error: {0}")]
+    Avro(#[source] Box<apache_avro::Error>),
+
+    #[error("Excel error: {0}")]
+    Excel(#[from] calamine::Error),
+
+    #[error("Xlsx error: {0}")]
+    Xlsx(#[from] XlsxError),
+
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+// The largest sources are boxed to keep `Result<_, DfKitError>` small
+impl From<DataFusionError> for DfKitError {
+    fn from(error: DataFusionError) -> Self {
+        DfKitError::DataFusion(Box::new(error))
+    }
+}
+
+impl From<apache_avro::Error> for DfKitError {
+    fn from(error: apache_avro::Error) -> Self {
+        DfKitError::Avro(Box::new(error))
+    }
 }
 
 pub fn file_type(file_path: &Path) -> Result<FileFormat, FileParseError> {
+    if file_path
+        .to_str()
+        .is_some_and(|path| path.starts_with("sqlite://"))
+    {
+        return Ok(FileFormat::Sqlite);
+    }
     match Path::new(file_path)
         .extension()
         .and_then(|ext| ext.to_str())
     {
-        Some("csv") => Ok(FileFormat::Csv),
-        Some("parquet") => Ok(FileFormat::Parquet),
-        Some("json") => Ok(FileFormat::Json),
-        Some("avro") => Ok(FileFormat::Avro),
-        Some(_) => Err(FileParseError::UnsupportedFileFormat),
+        Some(ext) => format_from_extension(ext),
         None => Err(FileParseError::InvalidExtension),
     }
 }
 
+/// The format to read an input file as. A `--layout` reads every input as
+/// fixed-width, since extracts come as .txt, .dat and so on.
+pub fn input_format(ctx: &SessionContext, file_path: &Path) -> Result<FileFormat, FileParseError> {
+    let layout = ctx
+        .copied_config()
+        .get_extension::<ReadOptions>()
+        .is_some_and(|options| options.layout.is_some());
+    if layout {
+        return Ok(FileFormat::FixedWidth);
+    }
+    file_type(file_path)
+}
+
+pub fn format_from_extension(ext: &str) -> Result<FileFormat, FileParseError> {
+    match ext {
+        "csv" => Ok(FileFormat::Csv),
+        "parquet" => Ok(FileFormat::Parquet),
+        "json" => Ok(FileFormat::Json),
+        "avro" => Ok(FileFormat::Avro),
+        "xlsx" | "xlsm" | "xls" | "ods" => Ok(FileFormat::Excel),
+        "sqlite" | "sqlite3" | "db" => Ok(FileFormat::Sqlite),
+        "fwf" => Ok(FileFormat::FixedWidth),
+        _ => Err(FileParseError::UnsupportedFileFormat),
+    }
+}
+
+/// Parses `column [asc|desc] [nulls first|nulls last]`. Columns without an
+/// explicit direction use `descending`, and nulls sort last unless requested.
+pub fn parse_sort_spec(spec: &str, descending: bool) -> Result<SortSpec, DfKitError> {
+    let mut words: Vec<&str> = spec.split_whitespace().collect();
+    let keyword = |words: &[&str], back: usize| {
+        words
+            .len()
+            .checked_sub(back)
+            .map(|i| words[i].to_ascii_lowercase())
+    };
+
+    let mut nulls_first = false;
+    if keyword(&words, 2).as_deref() == Some("nulls") {
+        nulls_first = match keyword(&words, 1).as_deref() {
+            Some("first") => true,
+            Some("last") => false,
+            _ => {
+                return Err(DfKitError::CustomError(format!(
+                    "Invalid sort specification: {}",
+                    spec
+                )));
+            }
+        };
+        words.truncate(words.len() - 2);
+    }
+
+    let mut ascending = !descending;
+    if let Some(direction) = keyword(&words, 1).filter(|d| d == "asc" || d == "desc") {
+        ascending = direction == "asc";
+        words.pop();
+    }
+
+    if words.is_empty() {
+        return Err(DfKitError::CustomError(format!(
+            "Invalid sort specification: {}",
+            spec
+        )));
+    }
+
+    Ok(SortSpec {
+        column: words.join(" "),
+        ascending,
+        nulls_first,
+    })
+}
+
+/// Builds a runtime whose memory pool makes sorts, joins and aggregations
+/// spill to disk once `memory_limit` bytes are in use. Spill files go to
+/// `spill_dir` when given and the OS temp directory otherwise.
+pub fn build_runtime_env(
+    memory_limit: Option<usize>,
+    spill_dir: Option<PathBuf>,
+) -> Result<Arc<RuntimeEnv>, DfKitError> {
+    let disk_manager = match spill_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(&dir)?;
+            DiskManagerConfig::NewSpecified(vec![dir])
+        }
+        None => DiskManagerConfig::NewOs,
+    };
+    let mut builder = RuntimeEnvBuilder::new().with_disk_manager(disk_manager);
+    if let Some(limit) = memory_limit {
+        builder = builder.with_memory_pool(Arc::new(FairSpillPool::new(limit)));
+    }
+    Ok(builder.build_arc()?)
+}
+
+pub fn parse_name_style(style: &str) -> Result<NameStyle, DfKitError> {
+    match style {
+        "snake_case" => Ok(NameStyle::SnakeCase),
+        "lower" => Ok(NameStyle::Lower),
+        "upper" => Ok(NameStyle::Upper),
+        _ => Err(DfKitError::CustomError(format!(
+            "Unknown naming style: {}",
+            style
+        ))),
+    }
+}
+
 pub fn storage_type(file_path: &Path) -> Result<StorageType, DfKitError> {
     let path_str = file_path
         .to_str()
@@ -99,7 +488,7 @@ pub fn storage_type(file_path: &Path) -> Result<StorageType, DfKitError> {
         Ok(StorageType::S3)
     } else if path_str.starts_with("gs://") {
         Ok(StorageType::GCS)
-    } else if file_path.is_absolute() {
+    } else if path_str.starts_with("sqlite://") || file_path.is_absolute() {
         Ok(StorageType::Local)
     } else {
         Err(DfKitError::Storage(StorageTypeError::UnsupportedStorageType))
@@ -111,12 +500,32 @@ pub async fn register_table(
     ctx: &SessionContext,
     table_name: &str,
     file_path: &Path,
+) -> Result<DataFrame, DfKitError> {
+    register_table_with_overrides(ctx, table_name, file_path, true).await
+}
+
+/// Registers a file that a command reads alongside its primary input, such as
+/// the other side of a diff. Schema overrides only apply to the columns this
+/// file has, so one `--types` list can serve inputs with different columns.
+pub async fn register_secondary_table(
+    ctx: &SessionContext,
+    table_name: &str,
+    file_path: &Path,
+) -> Result<DataFrame, DfKitError> {
+    register_table_with_overrides(ctx, table_name, file_path, false).await
+}
+
+async fn register_table_with_overrides(
+    ctx: &SessionContext,
+    table_name: &str,
+    file_path: &Path,
+    strict_overrides: bool,
 ) -> Result<DataFrame, DfKitError> {
     let storage_type = storage_type(file_path)?;
     let (file_format, file_name): (FileFormat, String) = match storage_type {
         StorageType::Local => {
             let path = file_path.to_path_buf();
-            let file_format = file_type(&path)?;
+            let file_format = input_format(ctx, &path)?;
             let file_name = path.to_str()
                 .ok_or(DfKitError::FileParse(FileParseError::InvalidExtension))?
                 .to_string();
@@ -127,7 +536,7 @@ pub async fn register_table(
                 .to_str()
                 .ok_or(DfKitError::FileParse(FileParseError::InvalidExtension))?;
             let (_tmpfile, local_path) = download_to_tempfile(path_str).await?;
-            let file_format = file_type(&local_path)?;
+            let file_format = input_format(ctx, &local_path)?;
             let file_name = local_path
                 .to_str()
                 .ok_or(DfKitError::FileParse(FileParseError::InvalidExtension))?
@@ -147,7 +556,7 @@ pub async fn register_table(
             ctx.runtime_env()
                 .register_object_store(&url, store);
 
-            let file_format = file_type(&file_path.to_path_buf())?;
+            let file_format = input_format(ctx, file_path)?;
             (file_format, path_str.to_string())
         }
         StorageType::GCS => {
@@ -168,28 +577,143 @@ pub async fn register_table(
             ctx.runtime_env()
                 .register_object_store(&url, store);
 
-            let file_format = file_type(&file_path.to_path_buf())?;
+            let file_format = input_format(ctx, file_path)?;
             (file_format, path_str.to_string())
         }
     };
 
+    let read_options = ctx.copied_config().get_extension::<ReadOptions>();
+    let overrides = read_options
+        .as_ref()
+        .map(|options| options.schema_overrides.clone())
+        .unwrap_or_default();
+    let normalize_names = read_options
+        .as_ref()
+        .and_then(|options| options.normalize_names);
+    let json_path = read_options
+        .as_ref()
+        .and_then(|options| options.json_path.clone());
+    let sheet = read_options
+        .as_ref()
+        .and_then(|options| options.sheet.clone());
+    let range = read_options
+        .as_ref()
+        .and_then(|options| options.range.clone());
+    let table = read_options
+        .as_ref()
+        .and_then(|options| options.table.clone());
+    let layout = read_options.and_then(|options| options.layout.clone());
+    let schema;
+    let mut cast_overrides = false;
+
     match file_format {
         FileFormat::Csv => {
-            ctx.register_csv(table_name, &file_name, CsvReadOptions::default())
+            let mut options = CsvReadOptions::default();
+            if !overrides.is_empty() {
+                let inferred = ctx.read_csv(&file_name, CsvReadOptions::default()).await?;
+                let inferred = inferred.schema().as_arrow();
+                let overrides = overrides_for(inferred, &overrides, strict_overrides);
+                schema = apply_schema_overrides(inferred, &overrides)?;
+                options = options.schema(&schema);
+            }
+            ctx.register_csv(table_name, &file_name, options)
                 .await?;
         }
         FileFormat::Parquet => {
             ctx.register_parquet(table_name, &file_name, ParquetReadOptions::default())
                 .await?;
         }
+        FileFormat::Json if json_path.is_some() || !is_ndjson(&file_name, &storage_type)? => {
+            if !matches!(storage_type, StorageType::Local | StorageType::Url) {
+                return Err(DfKitError::CustomError(
+                    "JSON arrays and --json-path are only supported for local and HTTP files"
+                        .into(),
+                ));
+            }
+            let table = read_json_records(
+                &file_name,
+                json_path.as_deref(),
+                &overrides,
+                strict_overrides,
+                ctx.copied_config().batch_size(),
+            )?;
+            ctx.register_table(table_name, Arc::new(table))?;
+        }
         FileFormat::Json => {
+            // The NDJSON reader won't read numbers into strings, so overrides are cast below
             ctx.register_json(table_name, &file_name, NdJsonReadOptions::default())
                 .await?;
+            cast_overrides = true;
         }
         FileFormat::Avro => {
             ctx.register_avro(table_name, &file_name, AvroReadOptions::default())
                 .await?;
         }
+        FileFormat::Excel => {
+            if !matches!(storage_type, StorageType::Local | StorageType::Url) {
+                return Err(DfKitError::CustomError(
+                    "Spreadsheets are only supported for local and HTTP files".into(),
+                ));
+            }
+            let table = read_excel_sheet(&file_name, sheet.as_deref(), range.as_deref())?;
+            ctx.register_table(table_name, Arc::new(table))?;
+        }
+        FileFormat::Sqlite => {
+            if !matches!(storage_type, StorageType::Local | StorageType::Url) {
+                return Err(DfKitError::CustomError(
+                    "SQLite databases are only supported for local and HTTP files".into(),
+                ));
+            }
+            let batch_size = ctx.copied_config().batch_size();
+            let table = read_sqlite_table(Path::new(&file_name), table.as_deref(), batch_size)?;
+            ctx.register_table(table_name, Arc::new(table))?;
+        }
+        FileFormat::FixedWidth => {
+            if !matches!(storage_type, StorageType::Local | StorageType::Url) {
+                return Err(DfKitError::CustomError(
+                    "Fixed-width files are only supported for local and HTTP files".into(),
+                ));
+            }
+            let layout = layout.ok_or_else(|| {
+                DfKitError::CustomError("Fixed-width files need a --layout file".into())
+            })?;
+            let batch_size = ctx.copied_config().batch_size();
+            let table = read_fixed_width_file(&file_name, &layout, batch_size)?;
+            ctx.register_table(table_name, Arc::new(table))?;
+        }
+    }
+
+    // Formats that carry their own types get overrides applied as casts
+    if !overrides.is_empty()
+        && (cast_overrides
+            || matches!(
+                file_format,
+                FileFormat::Parquet
+                    | FileFormat::Avro
+                    | FileFormat::Excel
+                    | FileFormat::Sqlite
+                    | FileFormat::FixedWidth
+            ))
+    {
+        let df = ctx.table(table_name).await?;
+        let inferred = df.schema().as_arrow();
+        let overrides = overrides_for(inferred, &overrides, strict_overrides);
+        let target = apply_schema_overrides(inferred, &overrides)?;
+        let exprs = target
+            .fields()
+            .iter()
+            .map(|f| cast(ident(f.name()), f.data_type().clone()).alias(f.name()))
+            .collect::<Vec<_>>();
+        let view = df.select(exprs)?.into_view();
+        ctx.deregister_table(table_name)?;
+        ctx.register_table(table_name, view)?;
+    }
+
+    if let Some(style) = normalize_names {
+        let df = ctx.table(table_name).await?;
+        let view = normalize_columns(df, style)?.into_view();
+        ctx.deregister_table(table_name)?;
+        ctx.register_table(table_name, view)?;
     }
 
     Ok(ctx.table(table_name).await?)
@@ -224,56 +748,1747 @@ pub fn parse_file_list(
     }
 }
 
+/// Parses an Arrow type name such as `Utf8` or `Decimal128(18, 2)`, also
+/// accepting the shorthand `Timestamp(ms)` for a timestamp without time zone.
+pub fn parse_data_type(data_type: &str) -> Result<DataType, DfKitError> {
+    let data_type = data_type.trim();
+    let unit = data_type
+        .strip_prefix("Timestamp(")
+        .and_then(|rest| rest.strip_suffix(')'))
+        .and_then(|unit| match unit.trim() {
+            "s" => Some(TimeUnit::Second),
+            "ms" => Some(TimeUnit::Millisecond),
+            "us" => Some(TimeUnit::Microsecond),
+            "ns" => Some(TimeUnit::Nanosecond),
+            _ => None,
+        });
+
+    match unit {
+        Some(unit) => Ok(DataType::Timestamp(unit, None)),
+        None => Ok(data_type.parse::<DataType>()?),
+    }
+}
+
+/// Parses `name:Type` pairs separated by commas, e.g.
+/// `zip:Utf8,amount:Decimal128(18,2)`. Commas inside parentheses belong to
+/// the type.
+pub fn parse_type_overrides(types: &str) -> Result<Vec<Field>, DfKitError> {
+    let mut entries = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in types.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                entries.push(&types[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(&types[start..]);
+
+    entries
+        .into_iter()
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (name, data_type) = entry.split_once(':').ok_or_else(|| {
+                DfKitError::CustomError(format!("Expected name:Type, got: {}", entry))
+            })?;
+            Ok(Field::new(name.trim(), parse_data_type(data_type)?, true))
+        })
+        .collect()
+}
+
+pub fn load_schema_file(path: &Path) -> Result<Vec<Field>, DfKitError> {
+    let contents = std::fs::read_to_string(path)?;
+    let schema_file: SchemaFile = serde_json::from_str(&contents)?;
+    schema_file
+        .fields
+        .into_iter()
+        .map(|f| Ok(Field::new(f.name, parse_data_type(&f.data_type)?, f.nullable)))
+        .collect()
+}
+
+pub fn write_schema_file(schema: &Schema, path: &Path) -> Result<(), DfKitError> {
+    let schema_file = SchemaFile {
+        fields: schema
+            .fields()
+            .iter()
+            .map(|f| SchemaField {
+                name: f.name().to_string(),
+                data_type: f.data_type().to_string(),
+                nullable: f.is_nullable(),
+            })
+            .collect(),
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&schema_file)? + "\n")?;
+    Ok(())
+}
+
+/// Newline-delimited JSON has a complete object on its first non-blank line,
+/// unlike a top-level array or pretty-printed records.
+fn is_ndjson(file_name: &str, storage_type: &StorageType) -> Result<bool, DfKitError> {
+    if !matches!(storage_type, StorageType::Local | StorageType::Url) {
+        return Ok(true);
+    }
+    for line in BufReader::new(File::open(file_name)?).lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            return Ok(line.starts_with('{') && serde_json::from_str::<JsonValue>(line).is_ok());
+        }
+    }
+    Ok(true)
+}
+
+/// Reads JSON documents of any layout into an in-memory table. Each document is
+/// narrowed to `json_path` if given, and arrays are expanded into their records.
+/// The file is read twice, to infer the schema and then to decode it, so only
+/// one document is held as JSON values at a time.
+fn read_json_records(
+    file_name: &str,
+    json_path: Option<&str>,
+    overrides: &[Field],
+    strict_overrides: bool,
+    batch_size: usize,
+) -> Result<MemTable, DfKitError> {
+    let mut error = None;
+    let records = json_records(file_name, json_path)?.map_while(|record| match record {
+        Ok(record) => Some(Ok(record)),
+        Err(e) => {
+            error = Some(e);
+            None
+        }
+    });
+    let inferred = infer_json_schema_from_iterator(records)?;
+    if let Some(e) = error {
+        return Err(e);
+    }
+    let overrides = overrides_for(&inferred, overrides, strict_overrides);
+    let schema = Arc::new(apply_schema_overrides(&inferred, &overrides)?);
+
+    let mut decoder = JsonReaderBuilder::new(schema.clone())
+        .with_batch_size(batch_size)
+        .with_coerce_primitive(true)
+        .build_decoder()?;
+    let mut batches = vec![];
+    let mut rows = 0;
+    for record in json_records(file_name, json_path)? {
+        decoder.serialize(&[record?])?;
+        rows += 1;
+        if rows == batch_size {
+            batches.extend(decoder.flush()?);
+            rows = 0;
+        }
+    }
+    batches.extend(decoder.flush()?);
+    Ok(MemTable::try_new(schema, vec![batches])?)
+}
+
+/// Streams the records of a JSON file one document at a time, for
+/// `read_json_records`.
+fn json_records<'a>(
+    file_name: &str,
+    json_path: Option<&'a str>,
+) -> Result<impl Iterator<Item = Result<JsonValue, DfKitError>> + 'a, DfKitError> {
+    let reader = BufReader::new(File::open(file_name)?);
+    let documents = serde_json::Deserializer::from_reader(reader).into_iter::<JsonValue>();
+    let records = documents.flat_map(move |document| {
+        let mut value = match document {
+            Ok(value) => value,
+            Err(e) => return vec![Err(e.into())],
+        };
+        if let Some(json_path) = json_path {
+            for key in json_path.split('.') {
+                let next = match value {
+                    JsonValue::Object(mut fields) => fields.remove(key),
+                    JsonValue::Array(items) => key
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| items.into_iter().nth(i)),
+                    _ => None,
+                };
+                value = match next {
+                    Some(next) => next,
+                    None => {
+                        return vec![Err(DfKitError::CustomError(format!(
+                            "JSON path {} not found",
+                            json_path
+                        )))];
+                    }
+                };
+            }
+        }
+        match value {
+            JsonValue::Array(items) => items.into_iter().map(Ok).collect(),
+            value => vec![Ok(value)],
+        }
+    });
+
+    Ok(records.map(|record| match record {
+        Ok(record) if !record.is_object() => Err(DfKitError::CustomError(format!(
+            "Expected JSON objects as records, got: {}",
+            record
+        ))),
+        record => record,
+    }))
+}
+
+/// Reads one sheet of a spreadsheet into an in-memory table. The header is the
+/// first row with more than half of its cells filled, which skips title rows
+/// above it, and each column takes the narrowest type that fits all its cells.
+fn read_excel_sheet(
+    file_name: &str,
+    sheet: Option<&str>,
+    range: Option<&str>,
+) -> Result<MemTable, DfKitError> {
+    let mut workbook = open_workbook_auto(file_name)?;
+    let sheet_names = workbook.sheet_names();
+    let cells = match sheet {
+        None => workbook.worksheet_range_at(0),
+        Some(name) if sheet_names.iter().any(|n| n == name) => Some(workbook.worksheet_range(name)),
+        Some(index) => index
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| workbook.worksheet_range_at(i)),
+    }
+    .ok_or_else(|| {
+        DfKitError::CustomError(format!(
+            "Sheet {} not found, available sheets: {}",
+            sheet.unwrap_or("0"),
+            sheet_names.join(", ")
+        ))
+    })??;
+    let cells = match range {
+        Some(range) => {
+            let (start, end) = parse_cell_range(range)?;
+            cells.range(start, end)
+        }
+        None => cells,
+    };
+
+    let width = cells.width();
+    let filled = |row: &[ExcelData]| row.iter().filter(|cell| !cell.is_empty()).count();
+    let rows = cells.rows().collect::<Vec<_>>();
+    let header_index = rows
+        .iter()
+        .position(|row| filled(row) * 2 > width)
+        .unwrap_or(0);
+
+    let mut seen = HashSet::new();
+    let mut names = Vec::with_capacity(width);
+    for i in 0..width {
+        let header = rows
+            .get(header_index)
+            .map(|row| row[i].to_string().trim().to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| format!("column_{}", i + 1));
+        let mut name = header.clone();
+        let mut suffix = 2;
+        while !seen.insert(name.clone()) {
+            name = format!("{}_{}", header, suffix);
+            suffix += 1;
+        }
+        names.push(name);
+    }
+
+    let data = rows
+        .iter()
+        .skip(header_index + 1)
+        .filter(|row| filled(row) > 0)
+        .collect::<Vec<_>>();
+    let mut fields = Vec::with_capacity(width);
+    let mut columns = Vec::with_capacity(width);
+    for (i, name) in names.into_iter().enumerate() {
+        let column = data.iter().map(|row| &row[i]).collect::<Vec<_>>();
+        let array = excel_column(&column);
+        fields.push(Field::new(name, array.data_type().clone(), true));
+        columns.push(array);
+    }
+
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new_with_options(
+        schema.clone(),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(data.len())),
+    )?;
+    Ok(MemTable::try_new(schema, vec![vec![batch]])?)
+}
+
+fn excel_datetime(cell: &ExcelData) -> Option<NaiveDateTime> {
+    match cell {
+        ExcelData::DateTime(datetime) if datetime.is_datetime() => datetime.as_datetime(),
+        ExcelData::DateTimeIso(text) => text.parse::<NaiveDateTime>().ok().or_else(|| {
+            text.parse::<NaiveDate>()
+                .ok()
+                .map(|date| date.and_time(NaiveTime::MIN))
+        }),
+        _ => None,
+    }
+}
+
+/// Builds an Arrow array for one spreadsheet column: booleans, whole numbers,
+/// numbers, dates and datetimes when every filled cell agrees, text otherwise.
+fn excel_column(cells: &[&ExcelData]) -> ArrayRef {
+    let values = cells
+        .iter()
+        .filter(|cell| !matches!(cell, ExcelData::Empty | ExcelData::Error(_)))
+        .collect::<Vec<_>>();
+    let all = |predicate: fn(&ExcelData) -> bool| {
+        !values.is_empty() && values.iter().all(|cell| predicate(cell))
+    };
+
+    if all(|cell| matches!(cell, ExcelData::Bool(_))) {
+        Arc::new(BooleanArray::from_iter(cells.iter().map(
+            |cell| match cell {
+                ExcelData::Bool(b) => Some(*b),
+                _ => None,
+            },
+        )))
+    } else if all(|cell| match cell {
+        ExcelData::Int(_) => true,
+        ExcelData::Float(f) => f.fract() == 0.0 && f.abs() < i64::MAX as f64,
+        _ => false,
+    }) {
+        Arc::new(Int64Array::from_iter(cells.iter().map(|cell| match cell {
+            ExcelData::Int(i) => Some(*i),
+            ExcelData::Float(f) => Some(*f as i64),
+            _ => None,
+        })))
+    } else if all(|cell| matches!(cell, ExcelData::Int(_) | ExcelData::Float(_))) {
+        Arc::new(Float64Array::from_iter(
+            cells
+                .iter()
+                .map(|cell| cell.as_f64().filter(|_| !cell.is_empty())),
+        ))
+    } else if all(|cell| excel_datetime(cell).is_some_and(|dt| dt.time() == NaiveTime::MIN)) {
+        Arc::new(Date32Array::from_iter(cells.iter().map(|cell| {
+            excel_datetime(cell).map(|dt| Date32Type::from_naive_date(dt.date()))
+        })))
+    } else if all(|cell| excel_datetime(cell).is_some()) {
+        Arc::new(TimestampMillisecondArray::from_iter(cells.iter().map(
+            |cell| excel_datetime(cell).map(|dt| dt.and_utc().timestamp_millis()),
+        )))
+    } else {
+        Arc::new(StringArray::from_iter(cells.iter().map(|cell| {
+            match cell {
+                ExcelData::Empty | ExcelData::Error(_) => None,
+                cell => Some(
+                    excel_datetime(cell)
+                        .map(|dt| dt.to_string())
+                        .unwrap_or_else(|| cell.to_string()),
+                ),
+            }
+        })))
+    }
+}
+
+/// Zero-based (row, column) position of a spreadsheet cell.
+pub type CellPosition = (u32, u32);
+
+/// Parses an A1-style range such as `B3:F200` into its top-left and
+/// bottom-right cells.
+pub fn parse_cell_range(range: &str) -> Result<(CellPosition, CellPosition), DfKitError> {
+    let invalid = || DfKitError::CustomError(format!("Invalid cell range: {}", range));
+    let cell = |cell: &str| {
+        let cell = cell.trim().to_ascii_uppercase();
+        let split = cell
+            .find(|c: char| c.is_ascii_digit())
+            .filter(|&i| i > 0)
+            .ok_or_else(invalid)?;
+        let (letters, digits) = cell.split_at(split);
+        if !letters.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(invalid());
+        }
+        let column = letters
+            .chars()
+            .fold(0u32, |acc, c| acc * 26 + (c as u32 - 'A' as u32 + 1));
+        let row = digits
+            .parse::<u32>()
+            .ok()
+            .filter(|&row| row > 0)
+            .ok_or_else(invalid)?;
+        Ok((row - 1, column - 1))
+    };
+
+    let (start, end) = range.split_once(':').ok_or_else(invalid)?;
+    let (start, end) = (cell(start)?, cell(end)?);
+    if start.0 > end.0 || start.1 > end.1 {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+/// Splits `sqlite:///path/db.sqlite?table=orders` into the database path and
+/// table. Plain paths name no table.
+pub fn parse_sqlite_source(file_path: &Path) -> Result<(PathBuf, Option<String>), DfKitError> {
+    let path_str = file_path
+        .to_str()
+        .ok_or(DfKitError::FileParse(FileParseError::InvalidExtension))?;
+    if !path_str.starts_with("sqlite://") {
+        return Ok((file_path.to_path_buf(), None));
+    }
+
+    let url = Url::parse(path_str)?;
+    let path = url.to_file_path().map_err(|_| {
+        DfKitError::CustomError(format!(
+            "Expected sqlite:///absolute/path, got: {}",
+            path_str
+        ))
+    })?;
+    let table = url
+        .query_pairs()
+        .find(|(key, _)| key == "table")
+        .map(|(_, table)| table.into_owned());
+    Ok((path, table))
+}
+
+/// Picks the table to read: the one named by the source or `--table`, or the
+/// only table in the database.
+fn sqlite_read_table(
+    conn: &Connection,
+    file_path: &Path,
+    table: Option<&str>,
+) -> Result<String, DfKitError> {
+    let (_, source_table) = parse_sqlite_source(file_path)?;
+    if let Some(table) = source_table.or(table.map(String::from)) {
+        return Ok(table);
+    }
+
+    let tables = conn
+        .prepare(
+            "SELECT name FROM sqlite_master WHERE type IN ('table', 'view') \
+             AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        )?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    match tables.as_slice() {
+        [table] => Ok(table.clone()),
+        _ => Err(DfKitError::CustomError(format!(
+            "Specify a table with --table or ?table=, available tables: {}",
+            tables.join(", ")
+        ))),
+    }
+}
+
+/// Returns the `CREATE` statement SQLite stores for a table or view.
+pub fn sqlite_table_sql(file_path: &Path, table: Option<&str>) -> Result<String, DfKitError> {
+    let (path, _) = parse_sqlite_source(file_path)?;
+    let conn = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let table = sqlite_read_table(&conn, file_path, table)?;
+    Ok(conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE name = ?1",
+        [&table],
+        |row| row.get(0),
+    )?)
+}
+
+/// Reads a table with blocking I/O, handing each batch to the callback until it
+/// returns false.
+type BlockingRead =
+    dyn Fn(&SchemaRef, &mut dyn FnMut(RecordBatch) -> bool) -> Result<(), DfKitError> + Send + Sync;
+
+/// A partition for sources without an async reader. Each scan runs the reader
+/// on a blocking thread that feeds the stream through a small channel, and
+/// stops at the next batch once the stream is dropped.
+struct BlockingPartition {
+    schema: SchemaRef,
+    read: Arc<BlockingRead>,
+}
+
+impl BlockingPartition {
+    fn new<F>(schema: SchemaRef, read: F) -> Self
+    where
+        F: Fn(&SchemaRef, &mut dyn FnMut(RecordBatch) -> bool) -> Result<(), DfKitError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self {
+            schema,
+            read: Arc::new(read),
+        }
+    }
+}
+
+impl std::fmt::Debug for BlockingPartition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockingPartition")
+            .field("schema", &self.schema)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartitionStream for BlockingPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let builder = RecordBatchReceiverStreamBuilder::new(Arc::clone(&self.schema), 2);
+        let tx = builder.tx();
+        let schema = Arc::clone(&self.schema);
+        let read = Arc::clone(&self.read);
+        tokio::task::spawn_blocking(move || {
+            let mut send = |batch| tx.blocking_send(Ok(batch)).is_ok();
+            if let Err(e) = read(&schema, &mut send) {
+                let _ = tx.blocking_send(Err(DataFusionError::External(Box::new(e))));
+            }
+        });
+        builder.build()
+    }
+}
+
+/// Opens a SQLite table or view as a table that is read batch by batch when
+/// scanned, typing each column by its declared type.
+fn read_sqlite_table(
+    file_path: &Path,
+    table: Option<&str>,
+    batch_size: usize,
+) -> Result<StreamingTable, DfKitError> {
+    let (path, _) = parse_sqlite_source(file_path)?;
+    let conn = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let table = sqlite_read_table(&conn, file_path, table)?;
+
+    let fields = conn
+        .prepare("SELECT name, type FROM pragma_table_info(?1)")?
+        .query_map([&table], |row| {
+            Ok(Field::new(
+                row.get::<_, String>(0)?,
+                sqlite_type(&row.get::<_, String>(1)?),
+                true,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    if fields.is_empty() {
+        return Err(DfKitError::CustomError(format!(
+            "Table {} not found",
+            table
+        )));
+    }
+    let schema = Arc::new(Schema::new(fields));
+
+    let partition = BlockingPartition::new(Arc::clone(&schema), move |schema, send| {
+        read_sqlite_batches(&path, &table, schema, batch_size, send)
+    });
+    Ok(StreamingTable::try_new(schema, vec![Arc::new(partition)])?)
+}
+
+/// Reads a table's rows into batches of `batch_size` for a `BlockingPartition`.
+fn read_sqlite_batches(
+    path: &Path,
+    table: &str,
+    schema: &SchemaRef,
+    batch_size: usize,
+    mut send: impl FnMut(RecordBatch) -> bool,
+) -> Result<(), DfKitError> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut statement = conn.prepare(&format!("SELECT * FROM {}", quote_identifier(table)))?;
+    let mut rows = statement.query([])?;
+    let mut values = vec![Vec::with_capacity(batch_size); schema.fields().len()];
+    loop {
+        let row = rows.next()?;
+        if let Some(row) = row {
+            for (i, column) in values.iter_mut().enumerate() {
+                column.push(row.get::<_, SqlValue>(i)?);
+            }
+        }
+        if values[0].len() == batch_size || (row.is_none() && !values[0].is_empty()) {
+            let columns = schema
+                .fields()
+                .iter()
+                .zip(values.iter_mut())
+                .map(|(field, column)| sqlite_column(&std::mem::take(column), field.data_type()))
+                .collect();
+            if !send(RecordBatch::try_new(schema.clone(), columns)?) {
+                break;
+            }
+        }
+        if row.is_none() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub fn load_fixed_width_layout(path: &Path) -> Result<FixedWidthLayout, DfKitError> {
+    let contents = std::fs::read_to_string(path)?;
+    let layout: FixedWidthLayout = serde_json::from_str(&contents)?;
+    if layout.columns.is_empty() {
+        return Err(DfKitError::CustomError("Layout has no columns".into()));
+    }
+    for column in &layout.columns {
+        if column.start == 0 || column.length == 0 {
+            return Err(DfKitError::CustomError(format!(
+                "Column {} needs a start of at least 1 and a non-zero length",
+                column.name
+            )));
+        }
+        parse_data_type(&column.data_type)?;
+    }
+    Ok(layout)
+}
+
+/// Opens a fixed-width text file as a table that is read batch by batch when
+/// scanned, slicing each line by the layout's character positions. Fields are
+/// trimmed, blank fields are null, and values that don't parse as the column's
+/// type are an error.
+fn read_fixed_width_file(
+    file_name: &str,
+    layout: &FixedWidthLayout,
+    batch_size: usize,
+) -> Result<StreamingTable, DfKitError> {
+    let fields = layout
+        .columns
+        .iter()
+        .map(|c| Ok(Field::new(&c.name, parse_data_type(&c.data_type)?, true)))
+        .collect::<Result<Vec<_>, DfKitError>>()?;
+    let schema = Arc::new(Schema::new(fields));
+    // Report a missing file when registering rather than when scanning
+    File::open(file_name)?;
+
+    let path = PathBuf::from(file_name);
+    let layout = layout.clone();
+    let partition = BlockingPartition::new(Arc::clone(&schema), move |schema, send| {
+        read_fixed_width_batches(&path, &layout, schema, batch_size, send)
+    });
+    Ok(StreamingTable::try_new(schema, vec![Arc::new(partition)])?)
+}
+
+/// Reads the lines of a fixed-width file into batches of `batch_size`. Blank
+/// lines are skipped but still counted, so errors name the line as an editor
+/// would show it.
+fn read_fixed_width_batches(
+    path: &Path,
+    layout: &FixedWidthLayout,
+    schema: &SchemaRef,
+    batch_size: usize,
+    mut send: impl FnMut(RecordBatch) -> bool,
+) -> Result<(), DfKitError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut builders = (0..layout.columns.len())
+        .map(|_| StringBuilder::new())
+        .collect::<Vec<_>>();
+    let mut line_numbers = Vec::with_capacity(batch_size);
+    let mut lines = reader.lines().enumerate();
+    loop {
+        let (line_number, line) = match lines.next() {
+            Some((i, line)) => (i + 1, Some(line?)),
+            None => (0, None),
+        };
+        if let Some(line) = line.as_deref().filter(|line| !line.trim().is_empty()) {
+            let line = line.trim_end_matches('\r');
+            for (column, builder) in layout.columns.iter().zip(builders.iter_mut()) {
+                let value = fixed_width_field(line, column.start - 1, column.length).trim();
+                if value.is_empty() {
+                    builder.append_null();
+                } else {
+                    builder.append_value(value);
+                }
+            }
+            line_numbers.push(line_number);
+        }
+        if line_numbers.len() == batch_size || (line.is_none() && !line_numbers.is_empty()) {
+            let columns = schema
+                .fields()
+                .iter()
+                .zip(builders.iter_mut())
+                .map(|(field, builder)| fixed_width_column(&builder.finish(), field, &line_numbers))
+                .collect::<Result<Vec<_>, _>>()?;
+            line_numbers.clear();
+            if !send(RecordBatch::try_new(schema.clone(), columns)?) {
+                break;
+            }
+        }
+        if line.is_none() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The characters of `line` from zero-based character `start`, at most
+/// `length` of them, or an empty string past the end of the line.
+fn fixed_width_field(line: &str, start: usize, length: usize) -> &str {
+    let mut offsets = line.char_indices().map(|(i, _)| i).chain([line.len()]);
+    match offsets.nth(start) {
+        Some(begin) => {
+            let end = offsets.nth(length - 1).unwrap_or(line.len());
+            &line[begin..end]
+        }
+        None => "",
+    }
+}
+
+/// Casts a column of trimmed fields to its layout type, naming the line and
+/// column of the first value that doesn't parse.
+fn fixed_width_column(
+    strings: &StringArray,
+    field: &Field,
+    line_numbers: &[usize],
+) -> Result<ArrayRef, DfKitError> {
+    let options = CastOptions {
+        safe: false,
+        ..Default::default()
+    };
+    cast_with_options(strings, field.data_type(), &options).map_err(|e| {
+        let bad = (0..strings.len()).find(|&i| {
+            cast_with_options(&strings.slice(i, 1), field.data_type(), &options).is_err()
+        });
+        match bad {
+            Some(i) => DfKitError::CustomError(format!(
+                "Cannot cast value '{}' in column {} on line {} to {}",
+                strings.value(i),
+                field.name(),
+                line_numbers[i],
+                field.data_type()
+            )),
+            None => e.into(),
+        }
+    })
+}
+
+/// Maps a declared column type to Arrow by SQLite's affinity rules. Booleans,
+/// dates and timestamps have numeric affinity in SQLite, so they are told apart
+/// by name.
+fn sqlite_type(declared: &str) -> DataType {
+    let declared = declared.to_ascii_uppercase();
+    let has = |names: &[&str]| names.iter().any(|name| declared.contains(name));
+    if has(&["INT"]) {
+        DataType::Int64
+    } else if has(&["CHAR", "CLOB", "TEXT"]) || declared.is_empty() {
+        DataType::Utf8
+    } else if has(&["BLOB"]) {
+        DataType::Binary
+    } else if has(&["REAL", "FLOA", "DOUB"]) {
+        DataType::Float64
+    } else if has(&["BOOL"]) {
+        DataType::Boolean
+    } else if has(&["DATETIME", "TIMESTAMP"]) {
+        DataType::Timestamp(TimeUnit::Millisecond, None)
+    } else if has(&["DATE"]) {
+        DataType::Date32
+    } else {
+        DataType::Float64
+    }
+}
+
+fn sqlite_datetime(value: &SqlValue) -> Option<NaiveDateTime> {
+    match value {
+        SqlValue::Integer(seconds) => {
+            DateTime::from_timestamp(*seconds, 0).map(|dt| dt.naive_utc())
+        }
+        SqlValue::Text(text) => ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+            .or_else(|| {
+                NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                    .ok()
+                    .map(|date| date.and_time(NaiveTime::MIN))
+            }),
+        _ => None,
+    }
+}
+
+/// Converts SQLite values to the column's Arrow type. SQLite doesn't enforce
+/// declared types, so values that don't fit become null.
+fn sqlite_column(values: &[SqlValue], data_type: &DataType) -> ArrayRef {
+    match data_type {
+        DataType::Int64 => Arc::new(Int64Array::from_iter(values.iter().map(
+            |value| match value {
+                SqlValue::Integer(i) => Some(*i),
+                SqlValue::Real(f) => Some(*f as i64),
+                SqlValue::Text(text) => text.trim().parse().ok(),
+                _ => None,
+            },
+        ))),
+        DataType::Float64 => Arc::new(Float64Array::from_iter(values.iter().map(
+            |value| match value {
+                SqlValue::Integer(i) => Some(*i as f64),
+                SqlValue::Real(f) => Some(*f),
+                SqlValue::Text(text) => text.trim().parse().ok(),
+                _ => None,
+            },
+        ))),
+        DataType::Boolean => Arc::new(BooleanArray::from_iter(values.iter().map(
+            |value| match value {
+                SqlValue::Integer(i) => Some(*i != 0),
+                SqlValue::Real(f) => Some(*f != 0.0),
+                SqlValue::Text(text) => text.trim().to_ascii_lowercase().parse().ok(),
+                _ => None,
+            },
+        ))),
+        DataType::Date32 => Arc::new(Date32Array::from_iter(values.iter().map(|value| {
+            sqlite_datetime(value).map(|dt| Date32Type::from_naive_date(dt.date()))
+        }))),
+        DataType::Timestamp(_, _) => {
+            Arc::new(TimestampMillisecondArray::from_iter(values.iter().map(
+                |value| sqlite_datetime(value).map(|dt| dt.and_utc().timestamp_millis()),
+            )))
+        }
+        DataType::Binary => Arc::new(BinaryArray::from_iter(values.iter().map(
+            |value| match value {
+                SqlValue::Blob(bytes) => Some(bytes.as_slice()),
+                SqlValue::Text(text) => Some(text.as_bytes()),
+                _ => None,
+            },
+        ))),
+        _ => Arc::new(StringArray::from_iter(values.iter().map(
+            |value| match value {
+                SqlValue::Null => None,
+                SqlValue::Integer(i) => Some(i.to_string()),
+                SqlValue::Real(f) => Some(f.to_string()),
+                SqlValue::Text(text) => Some(text.clone()),
+                SqlValue::Blob(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            },
+        ))),
+    }
+}
+
+/// Declared SQLite type for an Arrow column, chosen so `sqlite_type` reads it
+/// back as the same type.
+fn sqlite_declared_type(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::Boolean => "BOOLEAN",
+        DataType::Date32 | DataType::Date64 => "DATE",
+        DataType::Timestamp(_, _) => "TIMESTAMP",
+        DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_) => "BLOB",
+        data_type if data_type.is_integer() => "INTEGER",
+        data_type if data_type.is_numeric() => "REAL",
+        _ => "TEXT",
+    }
+}
+
+fn sqlite_values(array: &ArrayRef) -> Result<Vec<SqlValue>, DfKitError> {
+    let values = match array.data_type() {
+        DataType::Boolean => {
+            let values = array.as_boolean();
+            (0..array.len())
+                .map(|i| SqlValue::Integer(values.value(i) as i64))
+                .collect()
+        }
+        DataType::Date32 | DataType::Date64 => {
+            let dates = arrow_cast(array, &DataType::Date32)?;
+            let dates = dates.as_primitive::<Date32Type>();
+            (0..array.len())
+                .map(|i| match dates.value_as_date(i) {
+                    Some(date) => SqlValue::Text(date.format("%Y-%m-%d").to_string()),
+                    None => SqlValue::Null,
+                })
+                .collect()
+        }
+        DataType::Timestamp(_, _) => {
+            let timestamps = arrow_cast(array, &DataType::Timestamp(TimeUnit::Millisecond, None))?;
+            let timestamps = timestamps.as_primitive::<TimestampMillisecondType>();
+            (0..array.len())
+                .map(|i| match timestamps.value_as_datetime(i) {
+                    Some(dt) => SqlValue::Text(dt.format("%Y-%m-%d %H:%M:%S%.3f").to_string()),
+                    None => SqlValue::Null,
+                })
+                .collect()
+        }
+        data_type if sqlite_declared_type(data_type) == "BLOB" => {
+            let bytes = arrow_cast(array, &DataType::Binary)?;
+            let bytes = bytes.as_binary::<i32>();
+            (0..array.len())
+                .map(|i| SqlValue::Blob(bytes.value(i).to_vec()))
+                .collect()
+        }
+        data_type if data_type.is_integer() => {
+            let integers = arrow_cast(array, &DataType::Int64)?;
+            let integers = integers.as_primitive::<Int64Type>();
+            (0..array.len())
+                .map(|i| SqlValue::Integer(integers.value(i)))
+                .collect()
+        }
+        data_type if data_type.is_numeric() => {
+            let numbers = arrow_cast(array, &DataType::Float64)?;
+            let numbers = numbers.as_primitive::<Float64Type>();
+            (0..array.len())
+                .map(|i| SqlValue::Real(numbers.value(i)))
+                .collect()
+        }
+        _ => (0..array.len())
+            .map(|i| array_value_to_string(array, i).map(SqlValue::Text))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    // Casts can null out values too, e.g. unsigned integers beyond i64
+    Ok(values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| match value {
+            _ if array.is_null(i) => SqlValue::Null,
+            value => value,
+        })
+        .collect())
+}
+
+/// The overrides to apply to a file with the given schema. Unless `strict`,
+/// overrides for columns the file lacks are dropped rather than rejected.
+fn overrides_for(inferred: &Schema, overrides: &[Field], strict: bool) -> Vec<Field> {
+    overrides
+        .iter()
+        .filter(|field| strict || inferred.field_with_name(field.name()).is_ok())
+        .cloned()
+        .collect()
+}
+
+/// Replaces the type (and nullability) of inferred fields with any override
+/// of the same name, keeping the inferred column order.
+pub fn apply_schema_overrides(
+    inferred: &Schema,
+    overrides: &[Field],
+) -> Result<Schema, DfKitError> {
+    for field in overrides {
+        if inferred.field_with_name(field.name()).is_err() {
+            return Err(DfKitError::CustomError(format!(
+                "Column {} in schema override not found in file",
+                field.name()
+            )));
+        }
+    }
+
+    let fields = inferred
+        .fields()
+        .iter()
+        .map(|f| match overrides.iter().find(|o| o.name() == f.name()) {
+            Some(o) => o.clone(),
+            None => f.as_ref().clone(),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Schema::new(fields))
+}
+
+pub fn load_validation_rules(path: &Path) -> Result<ValidationRules, DfKitError> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(serde_yaml::from_str(&contents)?)
+}
+
+pub fn read_parquet_metadata(path: &Path) -> Result<ParquetFileMeta, DfKitError> {
+    let reader = SerializedFileReader::new(File::open(path)?)?;
+    let metadata = reader.metadata();
+    let file_metadata = metadata.file_metadata();
+
+    let row_groups = metadata
+        .row_groups()
+        .iter()
+        .enumerate()
+        .map(|(index, row_group)| {
+            let columns = row_group
+                .columns()
+                .iter()
+                .map(|chunk| {
+                    let (min, max) = chunk
+                        .statistics()
+                        .map(statistics_bounds)
+                        .unwrap_or_default();
+                    ParquetColumnChunkMeta {
+                        column: chunk.column_path().string(),
+                        physical_type: chunk.column_type().to_string(),
+                        // The codec level is not stored in the file, so only the codec is shown
+                        compression: chunk
+                            .compression()
+                            .to_string()
+                            .split('(')
+                            .next()
+                            .unwrap_or_default()
+                            .to_string(),
+                        encodings: chunk.encodings().iter().map(|e| e.to_string()).collect(),
+                        compressed_size: chunk.compressed_size(),
+                        uncompressed_size: chunk.uncompressed_size(),
+                        min,
+                        max,
+                        null_count: chunk.statistics().and_then(|s| s.null_count_opt()),
+                        page_index: chunk.column_index_offset().is_some()
+                            && chunk.offset_index_offset().is_some(),
+                        bloom_filter: chunk.bloom_filter_offset().is_some(),
+                    }
+                })
+                .collect();
+            ParquetRowGroupMeta {
+                index,
+                num_rows: row_group.num_rows(),
+                total_byte_size: row_group.total_byte_size(),
+                compressed_size: row_group.compressed_size(),
+                columns,
+            }
+        })
+        .collect();
+
+    Ok(ParquetFileMeta {
+        version: file_metadata.version(),
+        created_by: file_metadata.created_by().map(str::to_string),
+        num_rows: file_metadata.num_rows(),
+        key_value_metadata: file_metadata
+            .key_value_metadata()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .map(|kv| (kv.key.clone(), kv.value.clone()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        row_groups,
+    })
+}
+
+/// Renders the physical min/max of a column chunk; byte arrays are shown as
+/// text when they hold UTF-8.
+fn statistics_bounds(statistics: &Statistics) -> (Option<String>, Option<String>) {
+    fn bytes(value: &[u8]) -> String {
+        match std::str::from_utf8(value) {
+            Ok(text) => text.to_string(),
+            Err(_) => value.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+
+    match statistics {
+        Statistics::Boolean(s) => (
+            s.min_opt().map(|v| v.to_string()),
+            s.max_opt().map(|v| v.to_string()),
+        ),
+        Statistics::Int32(s) => (
+            s.min_opt().map(|v| v.to_string()),
+            s.max_opt().map(|v| v.to_string()),
+        ),
+        Statistics::Int64(s) => (
+            s.min_opt().map(|v| v.to_string()),
+            s.max_opt().map(|v| v.to_string()),
+        ),
+        Statistics::Int96(s) => (
+            s.min_opt().map(|v| v.to_string()),
+            s.max_opt().map(|v| v.to_string()),
+        ),
+        Statistics::Float(s) => (
+            s.min_opt().map(|v| v.to_string()),
+            s.max_opt().map(|v| v.to_string()),
+        ),
+        Statistics::Double(s) => (
+            s.min_opt().map(|v| v.to_string()),
+            s.max_opt().map(|v| v.to_string()),
+        ),
+        Statistics::ByteArray(s) => (
+            s.min_opt().map(|v| bytes(v.data())),
+            s.max_opt().map(|v| bytes(v.data())),
+        ),
+        Statistics::FixedLenByteArray(s) => (
+            s.min_opt().map(|v| bytes(v.data())),
+            s.max_opt().map(|v| bytes(v.data())),
+        ),
+    }
+}
+
+/// Parses a human readable size such as `512MB`, `4GB` or `1024` into bytes.
+pub fn parse_byte_size(size: &str) -> Result<usize, DfKitError> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| DfKitError::CustomError(format!("Invalid size: {}", size)))?;
+    let multiplier: u64 = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => return Err(DfKitError::CustomError(format!("Invalid size: {}", size))),
+    };
+
+    Ok((number * multiplier as f64) as usize)
+}
+
+/// Renders an output file name from a template such as `{stem}_{n:04}.{ext}`
+/// or `{stem}_{value}.{ext}`. A `:0N` suffix zero-pads the chunk number.
+pub fn render_template(
+    template: &str,
+    stem: &str,
+    ext: &str,
+    n: Option<usize>,
+    value: Option<&str>,
+) -> Result<String, DfKitError> {
+    let mut rendered = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| {
+            DfKitError::CustomError(format!("Unclosed placeholder in template: {}", template))
+        })? + start;
+        let (name, spec) = match rest[start + 1..end].split_once(':') {
+            Some((name, spec)) => (name, Some(spec)),
+            None => (&rest[start + 1..end], None),
+        };
+
+        match (name, n, value) {
+            ("stem", _, _) => rendered.push_str(stem),
+            ("ext", _, _) => rendered.push_str(ext),
+            ("n", Some(n), _) => {
+                let width = spec
+                    .map(|w| w.parse::<usize>())
+                    .transpose()
+                    .map_err(|_| {
+                        DfKitError::CustomError(format!("Invalid width in template: {}", template))
+                    })?
+                    .unwrap_or(0);
+                rendered.push_str(&format!("{:0width$}", n, width = width));
+            }
+            ("value", _, Some(value)) => rendered.push_str(value),
+            _ => {
+                return Err(DfKitError::CustomError(format!(
+                    "Unsupported placeholder {{{}}} in template: {}",
+                    name, template
+                )));
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Turns a header like `Customer Name (US)` into an SQL-friendly identifier:
+/// punctuation and whitespace collapse to single underscores, and snake_case
+/// also splits camelCase words.
+pub fn normalize_column_name(name: &str, style: NameStyle) -> String {
+    let mut normalized = String::new();
+    let mut prev: Option<char> = None;
+    for ch in name.chars() {
+        if ch.is_alphanumeric() {
+            let word_boundary = style == NameStyle::SnakeCase
+                && ch.is_uppercase()
+                && prev.is_some_and(|p| p.is_lowercase() || p.is_numeric());
+            if word_boundary && !normalized.ends_with('_') {
+                normalized.push('_');
+            }
+            normalized.push(ch);
+        } else if !normalized.is_empty() && !normalized.ends_with('_') {
+            normalized.push('_');
+        }
+        prev = Some(ch);
+    }
+
+    let mut normalized = match style {
+        NameStyle::SnakeCase | NameStyle::Lower => normalized.trim_end_matches('_').to_lowercase(),
+        NameStyle::Upper => normalized.trim_end_matches('_').to_uppercase(),
+    };
+    if normalized.is_empty() {
+        normalized.push_str("column");
+    } else if normalized.starts_with(|c: char| c.is_numeric()) {
+        normalized.insert(0, '_');
+    }
+    normalized
+}
+
+/// Normalizes every name, suffixing `_2`, `_3`, ... when two names collide.
+pub fn normalize_column_names(names: &[String], style: NameStyle) -> Vec<String> {
+    let mut seen = HashSet::new();
+    names
+        .iter()
+        .map(|name| {
+            let base = normalize_column_name(name, style);
+            let mut candidate = base.clone();
+            let mut n = 2;
+            while !seen.insert(candidate.clone()) {
+                candidate = format!("{}_{}", base, n);
+                n += 1;
+            }
+            candidate
+        })
+        .collect()
+}
+
+/// Projects `df` so every column carries its normalized name.
+pub fn normalize_columns(df: DataFrame, style: NameStyle) -> Result<DataFrame, DfKitError> {
+    let names = df
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().to_string())
+        .collect::<Vec<_>>();
+    let exprs = names
+        .iter()
+        .zip(normalize_column_names(&names, style))
+        .map(|(name, normalized)| ident(name).alias(normalized))
+        .collect::<Vec<_>>();
+    Ok(df.select(exprs)?)
+}
+
+/// Matches a column name against a shell-style pattern where `*` matches any
+/// run of characters and `?` matches exactly one.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 pub async fn write_output(
     df: DataFrame,
     out_path: &Path,
     format: &FileFormat,
 ) -> Result<(), DfKitError> {
+    // Local files are written beside the target and only replace it once
+    // the query has run to completion
+    let output = match storage_type(out_path) {
+        Ok(StorageType::S3 | StorageType::GCS | StorageType::Url) => None,
+        _ => Some(PartialFile::new(out_path)),
+    };
+    let write_path = match &output {
+        Some(output) => output.partial.to_str().unwrap(),
+        None => out_path.to_str().unwrap(),
+    };
     match format {
         FileFormat::Csv => {
-            df.write_csv(
-                out_path.to_str().unwrap(),
-                DataFrameWriteOptions::default(),
-                None,
-            )
-            .await?
+            df.write_csv(write_path, DataFrameWriteOptions::default(), None)
+                .await?
         }
         FileFormat::Parquet => {
-            df.write_parquet(
-                out_path.to_str().unwrap(),
-                DataFrameWriteOptions::default(),
-                None,
-            )
-            .await?
+            let options = parquet_options(df.task_ctx().session_config(), df.schema().as_arrow())?;
+            df.write_parquet(write_path, DataFrameWriteOptions::default(), options)
+                .await?
+        }
+        FileFormat::Json if json_array(df.task_ctx().session_config()) => {
+            return write_batches(df, out_path, format).await;
         }
         FileFormat::Json => {
-            df.write_json(
-                out_path.to_str().unwrap(),
-                DataFrameWriteOptions::default(),
-                None,
-            )
-            .await?
+            df.write_json(write_path, DataFrameWriteOptions::default(), None)
+                .await?
         }
         FileFormat::Avro => {
-            return Err(DfKitError::DataFusion(DataFusionError::NotImplemented(
-                "Avro write not supported".into(),
-            )));
+            return Err(DataFusionError::NotImplemented("Avro write not supported".into()).into());
+        }
+        FileFormat::Excel => {
+            return write_batches(df, out_path, format).await;
+        }
+        FileFormat::Sqlite => {
+            return write_batches(df, out_path, format).await;
+        }
+        FileFormat::FixedWidth => {
+            return Err(
+                DataFusionError::NotImplemented("Fixed-width write not supported".into()).into(),
+            );
         }
     };
+    if let Some(output) = output {
+        output.commit()?;
+    }
+    Ok(())
+}
+
+/// Streams a `DataFrame` through a `BatchWriter`, for outputs DataFusion
+/// cannot write itself.
+async fn write_batches(
+    df: DataFrame,
+    out_path: &Path,
+    format: &FileFormat,
+) -> Result<(), DfKitError> {
+    let config = df.task_ctx().session_config().clone();
+    let mut stream = df.execute_stream().await?;
+    let mut writer = BatchWriter::try_new(out_path, format, &stream.schema(), &config)?;
+    while let Some(batch) = stream.next().await {
+        writer.write(&batch?)?;
+    }
+    writer.finish()?;
     Ok(())
 }
 
+/// The Parquet writer options for an output with the given schema. Per-column
+/// options such as bloom filters must name a column of the output.
+fn parquet_options(
+    config: &SessionConfig,
+    schema: &Schema,
+) -> Result<Option<TableParquetOptions>, DfKitError> {
+    let Some(options) = config.get_extension::<WriteOptions>() else {
+        return Ok(None);
+    };
+    for column in options.parquet.column_specific_options.keys() {
+        if schema.field_with_name(column).is_err() {
+            return Err(DfKitError::CustomError(format!(
+                "Column {} in --bloom-filter not found in output",
+                column
+            )));
+        }
+    }
+    Ok(Some(options.parquet.clone()))
+}
+
+fn json_array(config: &SessionConfig) -> bool {
+    config
+        .get_extension::<WriteOptions>()
+        .is_some_and(|options| options.json_array)
+}
+
+enum BatchSink {
+    Csv(Box<CsvWriter<File>>),
+    Json(LineDelimitedWriter<File>),
+    JsonArray(ArrayWriter<File>),
+    Parquet(Box<ArrowWriter<File>>, Box<WriterProperties>),
+    Excel(Box<ExcelSink>),
+    Sqlite(Box<SqliteSink>),
+}
+
+/// Appends rows to a SQLite table, creating it if it doesn't exist. The rows
+/// go in one transaction that commits when the writer finishes, so a failed
+/// write leaves the database as it was.
+struct SqliteSink {
+    conn: Connection,
+    insert: String,
+}
+
+impl SqliteSink {
+    fn try_new(
+        out_path: &Path,
+        schema: &SchemaRef,
+        config: &SessionConfig,
+    ) -> Result<Self, DfKitError> {
+        let (path, source_table) = parse_sqlite_source(out_path)?;
+        let table = source_table
+            .or_else(|| {
+                config
+                    .get_extension::<WriteOptions>()
+                    .and_then(|options| options.table.clone())
+            })
+            .or_else(|| {
+                path.file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+            })
+            .ok_or_else(|| DfKitError::CustomError("Specify a table with --table".into()))?;
+
+        let conn = Connection::open(&path)?;
+        conn.execute_batch("BEGIN")?;
+        let columns = schema
+            .fields()
+            .iter()
+            .map(|f| quote_identifier(f.name()))
+            .collect::<Vec<_>>();
+        let definitions = schema
+            .fields()
+            .iter()
+            .zip(&columns)
+            .map(|(f, column)| format!("{} {}", column, sqlite_declared_type(f.data_type())))
+            .collect::<Vec<_>>();
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} ({})",
+                quote_identifier(&table),
+                definitions.join(", ")
+            ),
+            [],
+        )?;
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote_identifier(&table),
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", ")
+        );
+        Ok(Self { conn, insert })
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), DfKitError> {
+        let values = batch
+            .columns()
+            .iter()
+            .map(sqlite_values)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut insert = self.conn.prepare_cached(&self.insert)?;
+        for row in 0..batch.num_rows() {
+            insert.execute(params_from_iter(values.iter().map(|column| &column[row])))?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), DfKitError> {
+        self.conn.execute_batch("COMMIT")?;
+        Ok(())
+    }
+}
+
+/// Rows in an Excel worksheet, including the header row.
+const EXCEL_MAX_ROWS: usize = 1 << 20;
+
+/// Excel stores numbers as doubles, so larger integers are written as text to
+/// keep every digit.
+const EXCEL_MAX_EXACT_INTEGER: u64 = 1 << 53;
+
+/// Streams rows into a single worksheet, saved as `.xlsx` when the writer
+/// finishes. The worksheet uses constant memory mode, which moves each row to a
+/// temporary file once the next one starts, so cells are written row by row.
+struct ExcelSink {
+    file: File,
+    workbook: Workbook,
+    next_row: u32,
+}
+
+impl ExcelSink {
+    fn try_new(file: File, schema: &SchemaRef) -> Result<Self, DfKitError> {
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet_with_constant_memory();
+        let bold = XlsxFormat::new().set_bold();
+        for (col, field) in schema.fields().iter().enumerate() {
+            worksheet.write_string_with_format(0, col as u16, field.name(), &bold)?;
+        }
+        worksheet.set_freeze_panes(1, 0)?;
+        Ok(Self {
+            file,
+            workbook,
+            next_row: 1,
+        })
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), DfKitError> {
+        if self.next_row as usize + batch.num_rows() > EXCEL_MAX_ROWS {
+            return Err(DfKitError::CustomError(format!(
+                "Output has more rows than a spreadsheet can hold ({} including the header)",
+                EXCEL_MAX_ROWS
+            )));
+        }
+
+        // Bring each column to the single type its kind of cell is written from
+        let columns = batch
+            .columns()
+            .iter()
+            .map(|array| {
+                let target = match array.data_type() {
+                    DataType::Date32 | DataType::Date64 => DataType::Date32,
+                    DataType::Timestamp(_, _) => DataType::Timestamp(TimeUnit::Millisecond, None),
+                    data_type if data_type.is_signed_integer() => DataType::Int64,
+                    data_type if data_type.is_unsigned_integer() => DataType::UInt64,
+                    data_type if data_type.is_numeric() => DataType::Float64,
+                    _ => return Ok(Arc::clone(array)),
+                };
+                arrow_cast(array, &target)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let date_format = XlsxFormat::new().set_num_format("yyyy-mm-dd");
+        let datetime_format = XlsxFormat::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+        let worksheet = self.workbook.worksheet_from_index(0)?;
+        for i in 0..batch.num_rows() {
+            let row = self.next_row + i as u32;
+            for (col, array) in columns.iter().enumerate() {
+                let col = col as u16;
+                if array.is_null(i) {
+                    continue;
+                }
+                match array.data_type() {
+                    DataType::Boolean => {
+                        worksheet.write_boolean(row, col, array.as_boolean().value(i))?;
+                    }
+                    DataType::Date32 => {
+                        let dates = array.as_primitive::<Date32Type>();
+                        if let Some(date) = dates.value_as_date(i) {
+                            worksheet.write_datetime_with_format(row, col, date, &date_format)?;
+                        }
+                    }
+                    DataType::Timestamp(_, _) => {
+                        let timestamps = array.as_primitive::<TimestampMillisecondType>();
+                        if let Some(datetime) = timestamps.value_as_datetime(i) {
+                            worksheet.write_datetime_with_format(
+                                row,
+                                col,
+                                datetime,
+                                &datetime_format,
+                            )?;
+                        }
+                    }
+                    DataType::Int64 => {
+                        let value = array.as_primitive::<Int64Type>().value(i);
+                        if value.unsigned_abs() <= EXCEL_MAX_EXACT_INTEGER {
+                            worksheet.write_number(row, col, value as f64)?;
+                        } else {
+                            worksheet.write_string(row, col, value.to_string())?;
+                        }
+                    }
+                    DataType::UInt64 => {
+                        let value = array.as_primitive::<UInt64Type>().value(i);
+                        if value <= EXCEL_MAX_EXACT_INTEGER {
+                            worksheet.write_number(row, col, value as f64)?;
+                        } else {
+                            worksheet.write_string(row, col, value.to_string())?;
+                        }
+                    }
+                    DataType::Float64 => {
+                        let value = array.as_primitive::<Float64Type>().value(i);
+                        worksheet.write_number(row, col, value)?;
+                    }
+                    _ => {
+                        worksheet.write_string(row, col, array_value_to_string(array, i)?)?;
+                    }
+                }
+            }
+        }
+        self.next_row += batch.num_rows() as u32;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), DfKitError> {
+        self.workbook.save_to_writer(self.file)?;
+        Ok(())
+    }
+}
+
+/// A hidden sibling of an output file that is written first and renamed into
+/// place once complete, so a failed write leaves any existing file untouched.
+struct PartialFile {
+    path: PathBuf,
+    partial: PathBuf,
+    committed: bool,
+}
+
+impl PartialFile {
+    fn new(path: &Path) -> Self {
+        let name = match (path.file_stem(), path.extension()) {
+            (Some(stem), Some(ext)) => format!(
+                ".{}.partial.{}",
+                stem.to_string_lossy(),
+                ext.to_string_lossy()
+            ),
+            _ => format!(
+                ".{}.partial",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ),
+        };
+        Self {
+            path: path.to_path_buf(),
+            partial: path.with_file_name(name),
+            committed: false,
+        }
+    }
+
+    fn commit(mut self) -> Result<PathBuf, DfKitError> {
+        std::fs::rename(&self.partial, &self.path)?;
+        self.committed = true;
+        Ok(self.path.clone())
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.partial);
+        }
+    }
+}
+
+/// Writes record batches to a single file as they arrive, for commands that
+/// stream their output rather than writing a whole `DataFrame`. The file only
+/// appears at `path` once the writer finishes.
+pub struct BatchWriter {
+    path: PathBuf,
+    output: Option<PartialFile>,
+    rows_written: usize,
+    sink: BatchSink,
+}
+
+impl BatchWriter {
+    pub fn try_new(
+        path: &Path,
+        format: &FileFormat,
+        schema: &SchemaRef,
+        config: &SessionConfig,
+    ) -> Result<Self, DfKitError> {
+        // The partial file is only created once the format is known to be
+        // writable, and SQLite appends in place inside a transaction instead
+        let output = PartialFile::new(path);
+        let create = || File::create(&output.partial);
+        let sink = match format {
+            FileFormat::Csv => BatchSink::Csv(Box::new(CsvWriter::new(create()?))),
+            FileFormat::Json if json_array(config) => {
+                BatchSink::JsonArray(ArrayWriter::new(create()?))
+            }
+            FileFormat::Json => BatchSink::Json(LineDelimitedWriter::new(create()?)),
+            FileFormat::Parquet => {
+                let properties = parquet_options(config, schema)?
+                    .map(|mut options| -> Result<_, DfKitError> {
+                        options.arrow_schema(schema);
+                        Ok(WriterPropertiesBuilder::try_from(&options)?)
+                    })
+                    .transpose()?
+                    .map(|builder| builder.build())
+                    .unwrap_or_default();
+                BatchSink::Parquet(
+                    Box::new(ArrowWriter::try_new(
+                        create()?,
+                        schema.clone(),
+                        Some(properties.clone()),
+                    )?),
+                    Box::new(properties),
+                )
+            }
+            FileFormat::Avro => {
+                return Err(
+                    DataFusionError::NotImplemented("Avro write not supported".into()).into(),
+                );
+            }
+            FileFormat::Excel if path.extension().is_some_and(|ext| ext != "xlsx") => {
+                return Err(DataFusionError::NotImplemented(
+                    "Only .xlsx spreadsheets can be written".into(),
+                )
+                .into());
+            }
+            FileFormat::Excel => BatchSink::Excel(Box::new(ExcelSink::try_new(create()?, schema)?)),
+            FileFormat::Sqlite => {
+                BatchSink::Sqlite(Box::new(SqliteSink::try_new(path, schema, config)?))
+            }
+            FileFormat::FixedWidth => {
+                return Err(DataFusionError::NotImplemented(
+                    "Fixed-width write not supported".into(),
+                )
+                .into());
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            output: (*format != FileFormat::Sqlite).then_some(output),
+            rows_written: 0,
+            sink,
+        })
+    }
+
+    pub fn write(&mut self, batch: &RecordBatch) -> Result<(), DfKitError> {
+        match &mut self.sink {
+            BatchSink::Csv(writer) => writer.write(batch)?,
+            BatchSink::Json(writer) => writer.write(batch)?,
+            BatchSink::JsonArray(writer) => writer.write(batch)?,
+            BatchSink::Excel(sink) => sink.write(batch)?,
+            BatchSink::Sqlite(sink) => sink.write(batch)?,
+            BatchSink::Parquet(writer, _) => writer.write(batch)?,
+        }
+        self.rows_written += batch.num_rows();
+        Ok(())
+    }
+
+    pub fn rows_written(&self) -> usize {
+        self.rows_written
+    }
+
+    /// Number of bytes writing `batch` next would add to the finished file,
+    /// found by encoding it on its own. Exact for CSV and JSON; for Parquet it
+    /// is an upper bound as long as every batch is flushed as its own row
+    /// group, since the standalone file repeats the magic bytes and schema.
+    pub fn encoded_size(&self, batch: &RecordBatch) -> Result<usize, DfKitError> {
+        if batch.num_rows() == 0 {
+            return Ok(0);
+        }
+        let mut buffer = Vec::new();
+        match &self.sink {
+            BatchSink::Csv(_) => {
+                let mut writer = CsvWriterBuilder::new()
+                    .with_header(self.rows_written == 0)
+                    .build(&mut buffer);
+                writer.write(batch)?;
+            }
+            BatchSink::Json(_) => {
+                let mut writer = LineDelimitedWriter::new(&mut buffer);
+                writer.write(batch)?;
+                writer.finish()?;
+            }
+            BatchSink::JsonArray(_) => {
+                let mut writer = ArrayWriter::new(&mut buffer);
+                writer.write(batch)?;
+                writer.finish()?;
+                // Appending replaces the opening bracket with a separator,
+                // and the closing bracket is already counted
+                if self.rows_written > 0 {
+                    return Ok(buffer.len() - 1);
+                }
+            }
+            BatchSink::Parquet(_, properties) => {
+                let mut writer =
+                    ArrowWriter::try_new(&mut buffer, batch.schema(), Some(*properties.clone()))?;
+                writer.write(batch)?;
+                writer.close()?;
+            }
+            BatchSink::Excel(_) | BatchSink::Sqlite(_) => {
+                return Err(DfKitError::CustomError(
+                    "Size-based splitting is not supported for spreadsheets or databases".into(),
+                ));
+            }
+        }
+        Ok(buffer.len())
+    }
+
+    /// Ends the current Parquet row group so the next batch starts a new one.
+    pub fn flush(&mut self) -> Result<(), DfKitError> {
+        if let BatchSink::Parquet(writer, _) = &mut self.sink {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn finish(self) -> Result<PathBuf, DfKitError> {
+        match self.sink {
+            BatchSink::Csv(writer) => drop(writer.into_inner()),
+            BatchSink::Json(mut writer) => writer.finish()?,
+            BatchSink::JsonArray(mut writer) => writer.finish()?,
+            BatchSink::Excel(sink) => sink.finish()?,
+            BatchSink::Sqlite(sink) => sink.finish()?,
+            BatchSink::Parquet(writer, _) => {
+                writer.close()?;
+            }
+        }
+        match self.output {
+            Some(output) => output.commit(),
+            None => Ok(self.path),
+        }
+    }
+}
+
 pub async fn download_to_tempfile(url: &str) -> Result<(NamedTempFile, PathBuf), DfKitError> {
     let response = Client::new().get(url).send().await?.bytes().await?;
 
     // Try to extract the file extension from the URL
     let ext = url
         .split('.')
-        .last()
+        .next_back()
         .and_then(|e| {
             let e = e.split('?').next().unwrap_or(e); // strip query string
             match e {
-                "csv" | "json" | "parquet" | "avro" => Some(e),
+                "csv" | "json" | "parquet" | "avro" | "xlsx" | "xlsm" | "xls" | "ods"
+                | "sqlite" | "sqlite3" | "db" | "fwf" | "txt" | "dat" => Some(e),
                 _ => None,
             }
         })
@@ -242,6 +242,100 @@ fn test_sort_command_descending() {
     ");
 }
 
+#[test]
+fn test_sort_command_per_column_specs() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "sales.csv",
+        "region,revenue\nW,\nE,10\nW,7\nE,\nE,30\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    cmd.args([
+        "sort",
+        input.to_str().unwrap(),
+        "--columns",
+        "region asc, revenue desc nulls last",
+    ]);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +--------+---------+
+    | region | revenue |
+    +--------+---------+
+    | E      | 30      |
+    | E      | 10      |
+    | E      |         |
+    | W      | 7       |
+    | W      |         |
+    +--------+---------+
+    ");
+}
+
+#[test]
+fn test_sort_command_with_memory_limit() {
+    let temp = tempdir().unwrap();
+    let input = create_extended_csv(temp.path());
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    cmd.args([
+        "sort",
+        input.to_str().unwrap(),
+        "--columns",
+        "name desc",
+        "--memory-limit",
+        "64MB",
+    ]);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +---------+-----+
+    | name    | age |
+    +---------+-----+
+    | charlie | 50  |
+    | bob     | 40  |
+    | alice   | 30  |
+    +---------+-----+
+    ");
+}
+
+#[test]
+fn test_sort_spills_input_larger_than_memory_limit() {
+    let temp = tempdir().unwrap();
+    let mut contents = String::from("id,name\n");
+    let mut expected = vec![];
+    for i in 0..300_000u64 {
+        let name = format!("name_{:06}", (i * 7919) % 300_000);
+        contents.push_str(&format!("{},{}\n", i, name));
+        expected.push(format!("{},{}", i, name));
+    }
+    let input = write_temp_file(temp.path(), "big.csv", &contents);
+    let output = temp.path().join("sorted.csv");
+    assert!(contents.len() > 4 * 1024 * 1024);
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "sort",
+            input.to_str().unwrap(),
+            "--columns",
+            "name desc",
+            "--memory-limit",
+            "1MB",
+            "-o",
+            output.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    expected.sort_by(|a, b| b.split(',').nth(1).cmp(&a.split(',').nth(1)));
+    let sorted = fs::read_to_string(&output).unwrap();
+    let lines = sorted.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "id,name");
+    assert_eq!(lines[1..], expected);
+}
+
 #[test]
 fn test_reverse_stdout() {
     let temp = tempdir().unwrap();
@@ -284,7 +378,7 @@ fn test_split_creates_chunks() {
     // Run the CLI command
     let _ = Command::cargo_bin("dfkit")
         .unwrap()
-        .args(&[
+        .args([
             "split",
             input_path.to_str().unwrap(),
             "--chunks",
@@ -322,7 +416,7 @@ fn test_cat_concatenates_csv_files() {
     // Run the CLI command
     let _ = Command::cargo_bin("dfkit")
         .unwrap()
-        .args(&[
+        .args([
             "cat",
             "--files",
             &input_files,
@@ -341,7 +435,7 @@ fn test_cat_concatenates_csv_files() {
     records.sort(); // Sort records alphabetically
 
     let sorted_result = std::iter::once(header)
-        .chain(records.into_iter())
+        .chain(records)
         .collect::<Vec<_>>()
         .join("\n");
 
@@ -362,7 +456,7 @@ fn test_url_file_split_to_local() {
 
     let _ = Command::cargo_bin("dfkit")
         .unwrap()
-        .args(&["split", url, "--chunks", "5", "--output", output_dir.to_str().unwrap()])
+        .args(["split", url, "--chunks", "5", "--output", output_dir.to_str().unwrap()])
         .assert()
         .success()
         .get_output()
@@ -401,3 +495,2525 @@ fn test_dedup_csv_file() {
     +-------+-----+
     ");
 }
+
+#[test]
+fn test_pivot_command() {
+    let temp = tempdir().unwrap();
+    let input_path = write_temp_file(
+        temp.path(),
+        "long.csv",
+        "date,metric,value\n2024-01-01,clicks,10\n2024-01-01,views,100\n2024-01-02,clicks,5\n2024-01-02,clicks,7\n2024-01-02,views,80\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "pivot",
+            input_path.to_str().unwrap(),
+            "--index",
+            "date",
+            "--columns",
+            "metric",
+            "--values",
+            "value",
+            "--agg",
+            "sum",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +------------+--------+-------+
+    | date       | clicks | views |
+    +------------+--------+-------+
+    | 2024-01-01 | 10     | 100   |
+    | 2024-01-02 | 12     | 80    |
+    +------------+--------+-------+
+    ");
+}
+
+#[test]
+fn test_unpivot_command() {
+    let temp = tempdir().unwrap();
+    let input_path = write_temp_file(
+        temp.path(),
+        "wide.csv",
+        "date,site,m_clicks,m_views\n2024-01-01,a,10,100\n2024-01-02,b,5,80\n",
+    );
+    let out_file = temp.path().join("long.csv");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "unpivot",
+            input_path.to_str().unwrap(),
+            "--id",
+            "date,site",
+            "--value-columns",
+            "m_*",
+            "--var-name",
+            "metric",
+            "--value-name",
+            "value",
+            "--output",
+            out_file.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let result_csv = fs::read_to_string(&out_file).unwrap();
+    let lines: Vec<&str> = result_csv.lines().collect();
+    let mut records = lines[1..].to_vec();
+    records.sort();
+
+    let sorted_result = std::iter::once(lines[0])
+        .chain(records)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    assert_snapshot!(sorted_result, @r"
+    date,site,metric,value
+    2024-01-01,a,m_clicks,10
+    2024-01-01,a,m_views,100
+    2024-01-02,b,m_clicks,5
+    2024-01-02,b,m_views,80
+    ");
+}
+
+#[test]
+fn test_dedup_keep_last_with_report() {
+    let temp = tempdir().unwrap();
+    let input_path = write_temp_file(
+        temp.path(),
+        "users.csv",
+        "id,email,updated_at\n1,a@x.com,2024-01-01\n1,a@x.com,2024-03-01\n2,b@x.com,2024-02-01\n1,a@x.com,2024-02-01\n",
+    );
+    let out_file = temp.path().join("deduped.csv");
+    let report_file = temp.path().join("removed.csv");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "dedup",
+            "--filename",
+            input_path.to_str().unwrap(),
+            "--on",
+            "id,email",
+            "--keep",
+            "last",
+            "--order-by",
+            "updated_at",
+            "--report",
+            report_file.to_str().unwrap(),
+            "--output",
+            out_file.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let sorted_lines = |path: &Path| {
+        let contents = fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        let mut records = lines[1..].to_vec();
+        records.sort();
+        std::iter::once(lines[0])
+            .chain(records)
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    assert_snapshot!(sorted_lines(&out_file), @r"
+    id,email,updated_at
+    1,a@x.com,2024-03-01
+    2,b@x.com,2024-02-01
+    ");
+    assert_snapshot!(sorted_lines(&report_file), @r"
+    id,email,updated_at
+    1,a@x.com,2024-01-01
+    1,a@x.com,2024-02-01
+    ");
+}
+
+#[test]
+fn test_dedup_keep_by_file_position() {
+    let temp = tempdir().unwrap();
+    let input_path = write_temp_file(
+        temp.path(),
+        "users.csv",
+        "id,name\n2,bob\n1,alice\n2,robert\n1,alicia\n3,carol\n2,bobby\n",
+    );
+
+    let mut output = String::new();
+    for keep in ["first", "last"] {
+        let stdout = Command::cargo_bin("dfkit")
+            .unwrap()
+            .args([
+                "dedup",
+                "--filename",
+                input_path.to_str().unwrap(),
+                "--on",
+                "id",
+                "--keep",
+                keep,
+            ])
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        output.push_str(&String::from_utf8(stdout).unwrap());
+    }
+
+    assert_snapshot!(output, @r"
+    +----+-------+
+    | id | name  |
+    +----+-------+
+    | 2  | bob   |
+    | 1  | alice |
+    | 3  | carol |
+    +----+-------+
+    +----+--------+
+    | id | name   |
+    +----+--------+
+    | 1  | alicia |
+    | 3  | carol  |
+    | 2  | bobby  |
+    +----+--------+
+    ");
+}
+
+#[test]
+fn test_dedup_keep_none() {
+    let temp = tempdir().unwrap();
+    let input_path = write_temp_file(
+        temp.path(),
+        "users.csv",
+        "id,name\n1,alice\n1,alicia\n2,bob\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "dedup",
+            "--filename",
+            input_path.to_str().unwrap(),
+            "--on",
+            "id",
+            "--keep",
+            "none",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +----+------+
+    | id | name |
+    +----+------+
+    | 2  | bob  |
+    +----+------+
+    ");
+}
+
+#[test]
+fn test_reverse_spills_to_disk() {
+    let temp = tempdir().unwrap();
+    let input_path = create_extended_csv(temp.path());
+    let out_file = temp.path().join("reversed.csv");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "reverse",
+            input_path.to_str().unwrap(),
+            "--memory-limit",
+            "1B",
+            "--output",
+            out_file.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    assert_snapshot!(fs::read_to_string(&out_file).unwrap(), @r"
+    name,age
+    charlie,50
+    bob,40
+    alice,30
+    ");
+}
+
+#[test]
+fn test_query_with_memory_limit_and_spill_dir() {
+    let temp = tempdir().unwrap();
+    let input = create_extended_csv(temp.path());
+    let spill_dir = temp.path().join("spill");
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "--memory-limit",
+            "64MB",
+            "--spill-dir",
+            spill_dir.to_str().unwrap(),
+            "query",
+            input.to_str().unwrap(),
+            "--sql",
+            "SELECT age > 35 AS older, COUNT(*) AS n FROM t GROUP BY 1 ORDER BY 1",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert!(spill_dir.is_dir());
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +-------+---+
+    | older | n |
+    +-------+---+
+    | false | 1 |
+    | true  | 2 |
+    +-------+---+
+    ");
+}
+
+#[test]
+fn test_failed_query_leaves_no_output() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "input.csv", "name\n1\nbob\n");
+    let output = temp.path().join("out.csv");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "query",
+            input.to_str().unwrap(),
+            "--sql",
+            "SELECT arrow_cast(name, 'Int64') AS n FROM t",
+            "-o",
+            output.to_str().unwrap(),
+        ])
+        .assert()
+        .failure();
+
+    assert!(!output.exists());
+    assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_reverse_empty_file() {
+    let temp = tempdir().unwrap();
+    let input_path = write_temp_file(temp.path(), "empty.csv", "name,age\n");
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args(["reverse", input_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    ++
+    ++
+    ");
+}
+
+#[test]
+fn test_split_by_rows_with_template() {
+    let temp = tempdir().unwrap();
+    let input_path = temp.path().join("data.csv");
+    let output_dir = temp.path().join("out");
+    fs::write(
+        &input_path,
+        "name,age\nalice,30\nbob,40\ncharlie,25\ndave,20\nellen,45\n",
+    )
+    .unwrap();
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "split",
+            input_path.to_str().unwrap(),
+            "--rows",
+            "2",
+            "--template",
+            "{stem}_{n:04}.{ext}",
+            "--output",
+            output_dir.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let mut files = parse_file_list(None, Some(output_dir.clone())).unwrap();
+    files.sort();
+
+    let names: Vec<_> = files
+        .iter()
+        .map(|f| f.file_name().unwrap().to_str().unwrap().to_string())
+        .collect();
+    assert_eq!(names, ["data_0001.csv", "data_0002.csv", "data_0003.csv"]);
+    assert_snapshot!(fs::read_to_string(&files[2]).unwrap(), @r"
+    name,age
+    ellen,45
+    ");
+}
+
+#[test]
+fn test_split_by_column_value() {
+    let temp = tempdir().unwrap();
+    let input_path = write_temp_file(
+        temp.path(),
+        "orders.csv",
+        "customer_id,amount\na,10\nb,20\na,30\n",
+    );
+    let output_dir = temp.path().join("out");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "split",
+            input_path.to_str().unwrap(),
+            "--by",
+            "customer_id",
+            "--format",
+            "json",
+            "--output",
+            output_dir.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    assert_snapshot!(fs::read_to_string(output_dir.join("orders_a.json")).unwrap(), @r#"
+    {"customer_id":"a","amount":10}
+    {"customer_id":"a","amount":30}
+    "#);
+    assert!(output_dir.join("orders_b.json").exists());
+}
+
+#[test]
+fn test_split_by_column_value_name_collision() {
+    let temp = tempdir().unwrap();
+    let input_path = write_temp_file(temp.path(), "paths.csv", "dir,n\na/b,1\na_b,2\n");
+    let output_dir = temp.path().join("out");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "split",
+            input_path.to_str().unwrap(),
+            "--by",
+            "dir",
+            "--output",
+            output_dir.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Values 'a/b' and 'a_b' of column dir both map to file name paths_a_b.csv",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_split_by_column_value_many_groups() {
+    let temp = tempdir().unwrap();
+    let mut contents = String::from("id,amount\n");
+    for i in 0..200 {
+        contents.push_str(&format!("{},{}\n", i % 100, i));
+    }
+    let input_path = write_temp_file(temp.path(), "orders.csv", &contents);
+    let output_dir = temp.path().join("out");
+
+    // More groups than the process may hold open files
+    Command::new("sh")
+        .args([
+            "-c",
+            "ulimit -n 64 && exec \"$0\" \"$@\"",
+            assert_cmd::cargo::cargo_bin("dfkit").to_str().unwrap(),
+            "split",
+            input_path.to_str().unwrap(),
+            "--by",
+            "id",
+            "--output",
+            output_dir.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let files = parse_file_list(None, Some(output_dir.clone())).unwrap();
+    assert_eq!(files.len(), 100);
+    assert_snapshot!(fs::read_to_string(output_dir.join("orders_42.csv")).unwrap(), @r"
+    id,amount
+    42,42
+    42,142
+    ");
+}
+
+#[test]
+fn test_split_by_max_size() {
+    let temp = tempdir().unwrap();
+    let mut contents = String::from("id,name\n");
+    for i in 0..100 {
+        contents.push_str(&format!("{},name_{}\n", i, i));
+    }
+    let input_path = write_temp_file(temp.path(), "big.csv", &contents);
+    let output_dir = temp.path().join("out");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "split",
+            input_path.to_str().unwrap(),
+            "--max-size",
+            "500B",
+            "--output",
+            output_dir.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let files = parse_file_list(None, Some(output_dir.clone())).unwrap();
+    assert!(files.len() > 1);
+    for file in &files {
+        assert!(fs::metadata(file).unwrap().len() <= 500);
+    }
+
+    let total_rows: usize = files
+        .iter()
+        .map(|f| fs::read_to_string(f).unwrap().lines().count() - 1)
+        .sum();
+    assert_eq!(total_rows, 100);
+}
+
+#[test]
+fn test_cat_union_by_name_with_source_column() {
+    let temp = tempdir().unwrap();
+    let file1 = write_temp_file(temp.path(), "part1.csv", "name,age\nalice,30\n");
+    let file2 = write_temp_file(
+        temp.path(),
+        "part2.csv",
+        "age,name,city\n25.5,charlie,paris\n",
+    );
+    let out_file = temp.path().join("combined.csv");
+
+    let input_files = format!("{},{}", file1.display(), file2.display());
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "cat",
+            "--files",
+            &input_files,
+            "--union-by-name",
+            "--add-source-column",
+            "source_file",
+            "--output",
+            out_file.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let result_csv = fs::read_to_string(&out_file)
+        .unwrap()
+        .replace(temp.path().to_str().unwrap(), "<dir>");
+    let lines: Vec<&str> = result_csv.lines().collect();
+    let mut records = lines[1..].to_vec();
+    records.sort();
+
+    let sorted_result = std::iter::once(lines[0])
+        .chain(records)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    assert_snapshot!(sorted_result, @r"
+    name,age,city,source_file
+    alice,30.0,,<dir>/part1.csv
+    charlie,25.5,paris,<dir>/part2.csv
+    ");
+}
+
+#[test]
+fn test_diff_command() {
+    let temp = tempdir().unwrap();
+    let old = write_temp_file(
+        temp.path(),
+        "old.csv",
+        "id,name,age\n1,alice,30\n2,bob,40\n3,charlie,50\n",
+    );
+    let new = write_temp_file(
+        temp.path(),
+        "new.csv",
+        "id,name,age\n1,alice,30\n2,bob,41\n4,dave,20\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "diff",
+            old.to_str().unwrap(),
+            new.to_str().unwrap(),
+            "--key",
+            "id",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +-----------+------+
+    | _change   | rows |
+    +-----------+------+
+    | added     | 1    |
+    | changed   | 1    |
+    | removed   | 1    |
+    | unchanged | 1    |
+    +-----------+------+
+    +----+---------+-------------+------------+------------+-----------+
+    | id | _change | name_before | name_after | age_before | age_after |
+    +----+---------+-------------+------------+------------+-----------+
+    | 2  | changed | bob         | bob        | 40         | 41        |
+    | 3  | removed | charlie     |            | 50         |           |
+    | 4  | added   |             | dave       |            | 20        |
+    +----+---------+-------------+------------+------------+-----------+
+    ");
+}
+
+#[test]
+fn test_diff_null_keys() {
+    let temp = tempdir().unwrap();
+    let old = write_temp_file(temp.path(), "old.csv", "id,name\n1,alice\n,nobody\n");
+    let new = write_temp_file(temp.path(), "new.csv", "id,name\n1,alice\n");
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "diff",
+            old.to_str().unwrap(),
+            new.to_str().unwrap(),
+            "--key",
+            "id",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +-----------+------+
+    | _change   | rows |
+    +-----------+------+
+    | removed   | 1    |
+    | unchanged | 1    |
+    +-----------+------+
+    +----+---------+-------------+------------+
+    | id | _change | name_before | name_after |
+    +----+---------+-------------+------------+
+    |    | removed | nobody      |            |
+    +----+---------+-------------+------------+
+    ");
+}
+
+#[test]
+fn test_diff_types_override_column_in_one_file() {
+    let temp = tempdir().unwrap();
+    let old = write_temp_file(temp.path(), "old.csv", "id,zip\n1,02139\n2,10001\n");
+    let new = write_temp_file(temp.path(), "new.csv", "id\n1\n3\n");
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "diff",
+            old.to_str().unwrap(),
+            new.to_str().unwrap(),
+            "--key",
+            "id",
+            "--types",
+            "zip:Utf8",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    Columns not present in both files were not compared: zip
+    +-----------+------+
+    | _change   | rows |
+    +-----------+------+
+    | added     | 1    |
+    | removed   | 1    |
+    | unchanged | 1    |
+    +-----------+------+
+    +----+---------+
+    | id | _change |
+    +----+---------+
+    | 2  | removed |
+    | 3  | added   |
+    +----+---------+
+    ");
+}
+
+#[test]
+fn test_schema_diff_command() {
+    let temp = tempdir().unwrap();
+    let left = write_temp_file(temp.path(), "left.csv", "id,name,age\n1,alice,30\n");
+    let right = write_temp_file(
+        temp.path(),
+        "right.csv",
+        "name,id,age,city\nalice,1,30.5,paris\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "schema-diff",
+            left.to_str().unwrap(),
+            right.to_str().unwrap(),
+            "--fail-on",
+            "breaking",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("'breaking' level"))
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +-------------+---------------+--------+---------+----------+
+    | column_name | change        | before | after   | level    |
+    +-------------+---------------+--------+---------+----------+
+    | age         | type_changed  | Int64  | Float64 | breaking |
+    | city        | added         |        | Utf8    | warning  |
+    | id          | order_changed | 1      | 2       | info     |
+    | name        | order_changed | 2      | 1       | info     |
+    +-------------+---------------+--------+---------+----------+
+    ");
+}
+
+#[test]
+fn test_schema_diff_identical() {
+    let temp = tempdir().unwrap();
+    let left = create_basic_csv(temp.path());
+    let right = write_temp_file(temp.path(), "right.csv", "name,age\ncharlie,25\n");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "schema-diff",
+            left.to_str().unwrap(),
+            right.to_str().unwrap(),
+            "--fail-on",
+            "info",
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Schemas are identical"));
+}
+
+#[test]
+fn test_validate_command() {
+    let temp = tempdir().unwrap();
+    let countries = write_temp_file(temp.path(), "countries.csv", "code\nUS\nFR\n");
+    let input = write_temp_file(
+        temp.path(),
+        "people.csv",
+        "id,age,status,country\n1,30,active,US\n2,130,active,FR\n2,40,gone,DE\n",
+    );
+    let rules = write_temp_file(
+        temp.path(),
+        "rules.yaml",
+        &format!(
+            "row_count:\n  min: 1\ncolumns:\n  - name: id\n    not_null: true\n    unique: true\n  - name: age\n    range: {{ min: 0, max: 120 }}\n  - name: status\n    allowed_values: [active, inactive]\n  - name: country\n    foreign_key: {{ file: {}, column: code }}\n",
+            countries.display()
+        ),
+    );
+    let failures = temp.path().join("failures.csv");
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "validate",
+            input.to_str().unwrap(),
+            "--rules",
+            rules.to_str().unwrap(),
+            "--failures",
+            failures.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("4 of 6 rules did not pass"))
+        .get_output()
+        .stdout
+        .clone();
+
+    let report = String::from_utf8(output)
+        .unwrap()
+        .replace(temp.path().to_str().unwrap(), "<dir>");
+    assert_snapshot!(report, @r"
+    +---------+---------------------------------------+--------+--------------+
+    | column  | rule                                  | status | failing_rows |
+    +---------+---------------------------------------+--------+--------------+
+    | *       | row_count = 3                         | PASS   |              |
+    | id      | not_null                              | PASS   | 0            |
+    | id      | unique                                | FAIL   | 2            |
+    | age     | range >= 0 and <= 120                 | FAIL   | 1            |
+    | status  | allowed_values ('active', 'inactive') | FAIL   | 1            |
+    | country | foreign_key countries.csv.code        | FAIL   | 1            |
+    +---------+---------------------------------------+--------+--------------+
+    Failing rows written to: <dir>/failures.csv
+    ");
+    assert_eq!(fs::read_to_string(&failures).unwrap().lines().count(), 6);
+}
+
+#[test]
+fn test_view_with_type_overrides() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "zips.csv", "zip,amount\n01234,1.5\n99999,2.25\n");
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "view",
+            input.to_str().unwrap(),
+            "--types",
+            "zip:Utf8,amount:Decimal128(18,2)",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +-------+--------+
+    | zip   | amount |
+    +-------+--------+
+    | 01234 | 1.50   |
+    | 99999 | 2.25   |
+    +-------+--------+
+    ");
+}
+
+#[test]
+fn test_schema_export_and_reuse() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "zips.csv", "zip,city\n01234,boston\n");
+    let schema_path = temp.path().join("schema.json");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "schema",
+            input.to_str().unwrap(),
+            "--export",
+            schema_path.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let exported = fs::read_to_string(&schema_path).unwrap();
+    assert_snapshot!(exported, @r#"
+    {
+      "fields": [
+        {
+          "name": "zip",
+          "data_type": "Int64",
+          "nullable": true
+        },
+        {
+          "name": "city",
+          "data_type": "Utf8",
+          "nullable": true
+        }
+      ]
+    }
+    "#);
+
+    fs::write(&schema_path, exported.replace("Int64", "Utf8")).unwrap();
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "view",
+            input.to_str().unwrap(),
+            "--schema",
+            schema_path.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +-------+--------+
+    | zip   | city   |
+    +-------+--------+
+    | 01234 | boston |
+    +-------+--------+
+    ");
+}
+
+#[test]
+fn test_cast_safe_mode() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "raw.csv",
+        "amount,ts,flag\n1.234,2024-01-02 10:00,true\nabc,bad,false\n3,2024-03-04 11:30,\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "cast",
+            input.to_str().unwrap(),
+            "--types",
+            "amount:Decimal128(12,2),ts:Timestamp(ms),flag:Boolean",
+            "--safe",
+            "--datetime-format",
+            "%Y-%m-%d %H:%M",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +--------+------------------------------+--------+
+    | column | data_type                    | nulled |
+    +--------+------------------------------+--------+
+    | amount | Decimal128(12, 2)            | 1      |
+    | ts     | Timestamp(Millisecond, None) | 1      |
+    | flag   | Boolean                      | 0      |
+    +--------+------------------------------+--------+
+    +--------+---------------------+-------+
+    | amount | ts                  | flag  |
+    +--------+---------------------+-------+
+    | 1.23   | 2024-01-02T10:00:00 | true  |
+    |        |                     | false |
+    | 3.00   | 2024-03-04T11:30:00 |       |
+    +--------+---------------------+-------+
+    ");
+}
+
+#[test]
+fn test_cast_strict_mode_reports_row() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "raw.csv", "amount\n1.5\nabc\n");
+    let output = write_temp_file(temp.path(), "out.csv", "amount\n9.99\n");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "cast",
+            input.to_str().unwrap(),
+            "--types",
+            "amount:Decimal128(12,2)",
+            "--batch-size",
+            "1",
+            "-o",
+            output.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Cannot cast value 'abc' in column amount at row 2",
+        ));
+
+    // The first batch was written before the failure, but only to a
+    // partial file that is removed again
+    assert_eq!(fs::read_to_string(&output).unwrap(), "amount\n9.99\n");
+    assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn test_mutate_command() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "orders.csv",
+        "price,qty,ts\n2.5,4,2023-05-01T00:00:00\n10,1,2024-02-03T00:00:00\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "mutate",
+            input.to_str().unwrap(),
+            "--set",
+            "total=price*qty",
+            "--set",
+            "year=date_part('year', ts)",
+            "--set",
+            "qty=qty + 1",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +-------+-----+---------------------+-------+------+
+    | price | qty | ts                  | total | year |
+    +-------+-----+---------------------+-------+------+
+    | 2.5   | 5   | 2023-05-01T00:00:00 | 10.0  | 2023 |
+    | 10.0  | 2   | 2024-02-03T00:00:00 | 10.0  | 2024 |
+    +-------+-----+---------------------+-------+------+
+    ");
+}
+
+#[test]
+fn test_fillna_forward_by_partition() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "readings.csv",
+        "sensor,ts,value\na,2,\na,1,10\na,3,30\nb,1,\nb,2,5\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "fillna",
+            input.to_str().unwrap(),
+            "--strategy",
+            "forward",
+            "--order-by",
+            "ts",
+            "--partition-by",
+            "sensor",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +--------+----+-------+
+    | sensor | ts | value |
+    +--------+----+-------+
+    | a      | 1  | 10    |
+    | a      | 2  | 10    |
+    | a      | 3  | 30    |
+    | b      | 1  |       |
+    | b      | 2  | 5     |
+    +--------+----+-------+
+    ");
+}
+
+#[test]
+fn test_fillna_mean_with_output() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "readings.csv",
+        "sensor,value,label\na,10.0,x\na,,\na,20.0,y\nb,,z\nb,4.0,\n",
+    );
+    let out_file = temp.path().join("filled.csv");
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    cmd.args([
+        "fillna",
+        input.to_str().unwrap(),
+        "--strategy",
+        "mean",
+        "--partition-by",
+        "sensor",
+        "--output",
+        out_file.to_str().unwrap(),
+    ])
+    .assert()
+    .success();
+
+    let contents = fs::read_to_string(&out_file).unwrap();
+    let mut lines: Vec<&str> = contents.lines().collect();
+    lines[1..].sort_unstable();
+    assert_snapshot!(lines.join("\n"), @r"
+    sensor,value,label
+    a,10.0,x
+    a,15.0,
+    a,20.0,y
+    b,4.0,
+    b,4.0,z
+    ");
+}
+
+#[test]
+fn test_fillna_value() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "input.csv", "name,age\nalice,\n,40\n");
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "fillna",
+            input.to_str().unwrap(),
+            "--value",
+            "0",
+            "--columns",
+            "age",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +-------+-----+
+    | name  | age |
+    +-------+-----+
+    | alice | 0   |
+    |       | 40  |
+    +-------+-----+
+    ");
+}
+
+#[test]
+fn test_fillna_unknown_column() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "input.csv", "name,age\nalice,\n,40\n");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "fillna",
+            input.to_str().unwrap(),
+            "--value",
+            "0",
+            "--columns",
+            "age,nope",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Column nope not found"));
+}
+
+#[test]
+fn test_fillna_median_widens_integers() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "input.csv", "id,qty\n1,1\n2,\n3,2\n");
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "fillna",
+            input.to_str().unwrap(),
+            "--strategy",
+            "median",
+            "--columns",
+            "qty",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +----+-----+
+    | id | qty |
+    +----+-----+
+    | 1  | 1.0 |
+    | 2  | 1.5 |
+    | 3  | 2.0 |
+    +----+-----+
+    ");
+}
+
+#[test]
+fn test_replace_unknown_column() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "input.csv", "id,status\n1,A\n");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "replace",
+            input.to_str().unwrap(),
+            "--column",
+            "state",
+            "--map",
+            "A:active",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Column state not found"));
+}
+
+#[test]
+fn test_replace_command() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "accounts.csv",
+        "id,status\n1,A\n2,I\n3,X\n4,\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "replace",
+            input.to_str().unwrap(),
+            "--column",
+            "status",
+            "--map",
+            "A:active,I:inactive",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +----+----------+
+    | id | status   |
+    +----+----------+
+    | 1  | active   |
+    | 2  | inactive |
+    | 3  | X        |
+    | 4  |          |
+    +----+----------+
+    ");
+}
+
+#[test]
+fn test_rename_with_map() {
+    let temp = tempdir().unwrap();
+    let input = create_basic_csv(temp.path());
+    let out_file = temp.path().join("renamed.csv");
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    cmd.args([
+        "rename",
+        input.to_str().unwrap(),
+        "--map",
+        "name:first_name,age:years",
+        "--output",
+        out_file.to_str().unwrap(),
+    ])
+    .assert()
+    .success();
+
+    let contents = fs::read_to_string(&out_file).unwrap();
+    assert_snapshot!(contents, @r"
+    first_name,years
+    alice,30
+    bob,40
+    ");
+}
+
+#[test]
+fn test_rename_normalize() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "partner.csv",
+        "Customer Name (US),orderID,customer_name_us\nBob,1,x\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args(["rename", input.to_str().unwrap(), "--normalize", "snake_case"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +------------------+----------+--------------------+
+    | customer_name_us | order_id | customer_name_us_2 |
+    +------------------+----------+--------------------+
+    | Bob              | 1        | x                  |
+    +------------------+----------+--------------------+
+    ");
+}
+
+#[test]
+fn test_query_with_normalize_names() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "partner.csv",
+        "Customer Name (US),Order Total\nBob,10\nAnn,20\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "query",
+            input.to_str().unwrap(),
+            "--normalize-names",
+            "--sql",
+            "SELECT customer_name_us FROM t WHERE order_total > 15",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +------------------+
+    | customer_name_us |
+    +------------------+
+    | Ann              |
+    +------------------+
+    ");
+}
+
+#[test]
+fn test_top_overall() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "sales.csv",
+        "region,name,revenue\nE,a,10\nE,b,30\nE,c,20\nW,d,5\nW,e,\nW,f,7\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args(["top", input.to_str().unwrap(), "--n", "2", "--by", "revenue"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +--------+------+---------+
+    | region | name | revenue |
+    +--------+------+---------+
+    | E      | b    | 30      |
+    | E      | c    | 20      |
+    +--------+------+---------+
+    ");
+}
+
+#[test]
+fn test_top_per_group() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "sales.csv",
+        "region,name,revenue\nE,a,10\nE,b,30\nE,c,20\nW,d,5\nW,e,\nW,f,7\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "top",
+            input.to_str().unwrap(),
+            "--n",
+            "2",
+            "--by",
+            "revenue",
+            "--group-by",
+            "region",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +--------+------+---------+
+    | region | name | revenue |
+    +--------+------+---------+
+    | E      | b    | 30      |
+    | E      | c    | 20      |
+    | W      | f    | 7       |
+    | W      | d    | 5       |
+    +--------+------+---------+
+    ");
+}
+
+#[test]
+fn test_query_with_concurrency_options() {
+    let temp = tempdir().unwrap();
+    let input = create_basic_csv(temp.path());
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .env("DFKIT_BATCH_SIZE", "1024")
+        .args([
+            "--threads",
+            "2",
+            "query",
+            input.to_str().unwrap(),
+            "--sql",
+            "SELECT name, value FROM information_schema.df_settings \
+             WHERE name IN ('datafusion.execution.target_partitions', \
+             'datafusion.execution.batch_size') ORDER BY name",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +----------------------------------------+-------+
+    | name                                   | value |
+    +----------------------------------------+-------+
+    | datafusion.execution.batch_size        | 1024  |
+    | datafusion.execution.target_partitions | 2     |
+    +----------------------------------------+-------+
+    ");
+}
+
+#[test]
+fn test_zero_threads_is_rejected() {
+    let temp = tempdir().unwrap();
+    let input = create_basic_csv(temp.path());
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args(["count", input.to_str().unwrap(), "--threads", "0"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_convert_with_parquet_writer_options() {
+    use datafusion::parquet::basic::Compression;
+    use datafusion::parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "sales.csv",
+        "region,name,revenue\nE,a,10\nE,b,30\nW,c,20\n",
+    );
+    let out_file = temp.path().join("sales.parquet");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "convert",
+            input.to_str().unwrap(),
+            out_file.to_str().unwrap(),
+            "--parquet-compression",
+            "zstd(5)",
+            "--row-group-size",
+            "2",
+            "--no-dictionary",
+            "--statistics",
+            "chunk",
+            "--bloom-filter",
+            "region",
+            "--writer-version",
+            "2.0",
+            "--parquet-metadata",
+            "owner=data-eng",
+        ])
+        .assert()
+        .success();
+
+    let reader = SerializedFileReader::new(fs::File::open(&out_file).unwrap()).unwrap();
+    let metadata = reader.metadata();
+    assert_eq!(metadata.file_metadata().version(), 2);
+    assert_eq!(metadata.num_row_groups(), 2);
+
+    let owner = metadata
+        .file_metadata()
+        .key_value_metadata()
+        .unwrap()
+        .iter()
+        .find(|kv| kv.key == "owner")
+        .and_then(|kv| kv.value.clone());
+    assert_eq!(owner.as_deref(), Some("data-eng"));
+
+    let row_group = metadata.row_group(0);
+    let region = row_group.column(0);
+    // The codec level is not recorded in the file, only the codec itself
+    assert!(matches!(region.compression(), Compression::ZSTD(_)));
+    assert!(region.dictionary_page_offset().is_none());
+    assert!(region.statistics().is_some());
+    assert!(region.bloom_filter_offset().is_some());
+    assert!(row_group.column(1).bloom_filter_offset().is_none());
+}
+
+#[test]
+fn test_convert_bloom_filter_unknown_column() {
+    let temp = tempdir().unwrap();
+    let input = create_basic_csv(temp.path());
+    let out_file = temp.path().join("out.parquet");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "convert",
+            input.to_str().unwrap(),
+            out_file.to_str().unwrap(),
+            "--bloom-filter",
+            "nope",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Column nope in --bloom-filter not found in output",
+        ));
+    assert!(!out_file.exists());
+}
+
+#[test]
+fn test_convert_rejects_invalid_parquet_compression() {
+    let temp = tempdir().unwrap();
+    let input = create_basic_csv(temp.path());
+    let out_file = temp.path().join("out.parquet");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "convert",
+            input.to_str().unwrap(),
+            out_file.to_str().unwrap(),
+            "--parquet-compression",
+            "zstd(99)",
+        ])
+        .assert()
+        .failure();
+
+    assert!(!out_file.exists());
+}
+
+#[test]
+fn test_parquet_meta_command() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "sales.csv",
+        "region,revenue\nE,10\nE,30\nW,\n",
+    );
+    let parquet_file = temp.path().join("sales.parquet");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "convert",
+            input.to_str().unwrap(),
+            parquet_file.to_str().unwrap(),
+            "--row-group-size",
+            "2",
+            "--bloom-filter",
+            "region",
+            "--parquet-compression",
+            "snappy",
+        ])
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args(["parquet-meta", parquet_file.to_str().unwrap()])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +-----------------------+------------------------------------------------------------------+
+    | property              | value                                                            |
+    +-----------------------+------------------------------------------------------------------+
+    | format_version        | 1                                                                |
+    | created_by            | datafusion version 46.0.1                                        |
+    | num_rows              | 3                                                                |
+    | num_row_groups        | 2                                                                |
+    | metadata.ARROW:schema | /////6wAAAAQAAAAAAAKAAwACgAJAAQACgAAABAAAAAAAQQACAAIAAAABAAIA... |
+    +-----------------------+------------------------------------------------------------------+
+    +-----------+----------+-----------------+-----------------+
+    | row_group | num_rows | total_byte_size | compressed_size |
+    +-----------+----------+-----------------+-----------------+
+    | 0         | 2        | 138             | 146             |
+    | 1         | 1        | 94              | 101             |
+    +-----------+----------+-----------------+-----------------+
+    +-----------+---------+------------+-------------+--------------------------+-----------------+-------------------+-----+-----+------------+------------+--------------+
+    | row_group | column  | type       | compression | encodings                | compressed_size | uncompressed_size | min | max | null_count | page_index | bloom_filter |
+    +-----------+---------+------------+-------------+--------------------------+-----------------+-------------------+-----+-----+------------+------------+--------------+
+    | 0         | region  | BYTE_ARRAY | SNAPPY      | PLAIN,RLE,RLE_DICTIONARY | 60              | 56                | E   | E   | 0          | true       | true         |
+    | 0         | revenue | INT64      | SNAPPY      | PLAIN,RLE,RLE_DICTIONARY | 86              | 82                | 10  | 30  | 0          | true       | false        |
+    | 1         | region  | BYTE_ARRAY | SNAPPY      | PLAIN,RLE,RLE_DICTIONARY | 60              | 56                | W   | W   | 0          | true       | true         |
+    | 1         | revenue | INT64      | SNAPPY      | PLAIN,RLE,RLE_DICTIONARY | 41              | 38                |     |     | 1          | true       | false        |
+    +-----------+---------+------------+-------------+--------------------------+-----------------+-------------------+-----+-----+------------+------------+--------------+
+    ");
+}
+
+#[test]
+fn test_parquet_meta_json() {
+    let temp = tempdir().unwrap();
+    let input = create_basic_csv(temp.path());
+    let parquet_file = temp.path().join("people.parquet");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "convert",
+            input.to_str().unwrap(),
+            parquet_file.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "parquet-meta",
+            parquet_file.to_str().unwrap(),
+            "--output-format",
+            "json",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let meta: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(meta["num_rows"], 2);
+    let columns = &meta["row_groups"][0]["columns"];
+    assert_eq!(columns[0]["column"], "name");
+    assert_eq!(columns[0]["min"], "alice");
+    assert_eq!(columns[1]["max"], "40");
+    assert_eq!(columns[1]["null_count"], 0);
+}
+
+#[test]
+fn test_parquet_meta_rejects_object_store() {
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args(["parquet-meta", "s3://bucket/data.parquet"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "parquet-meta needs a local or HTTP Parquet file",
+        ));
+}
+
+#[test]
+fn test_schema_native_csv_shows_inference_evidence() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "zips.csv",
+        "zip,amount\n01234,1.5\n,2.25\n99999,\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args(["schema", input.to_str().unwrap(), "--native"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +-------------+---------------+--------------+------------+---------------+
+    | column_name | inferred_type | sampled_rows | null_count | sample_values |
+    +-------------+---------------+--------------+------------+---------------+
+    | zip         | Int64         | 3            | 1          | 01234, 99999  |
+    | amount      | Float64       | 3            | 1          | 1.5, 2.25     |
+    +-------------+---------------+--------------+------------+---------------+
+    ");
+}
+
+#[test]
+fn test_schema_native_parquet_with_field_ids() {
+    use datafusion::arrow::array::{Int32Array, RecordBatch, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::parquet::arrow::ArrowWriter;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    let temp = tempdir().unwrap();
+    let path = temp.path().join("ids.parquet");
+    let field_id = |id: &str| HashMap::from([("PARQUET:field_id".to_string(), id.to_string())]);
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false).with_metadata(field_id("1")),
+        Field::new("name", DataType::Utf8, true).with_metadata(field_id("2")),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec![Some("a"), None])),
+        ],
+    )
+    .unwrap();
+    let mut writer = ArrowWriter::try_new(fs::File::create(&path).unwrap(), schema, None).unwrap();
+    writer.write(&batch).unwrap();
+    writer.close().unwrap();
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args(["schema", path.to_str().unwrap(), "--native"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    message arrow_schema {
+      REQUIRED INT32 id [1];
+      OPTIONAL BYTE_ARRAY name [2] (STRING);
+    }
+    ");
+}
+
+#[test]
+fn test_schema_native_avro() {
+    use apache_avro::types::{Record, Value};
+    use apache_avro::{Schema, Writer};
+
+    let temp = tempdir().unwrap();
+    let path = temp.path().join("events.avro");
+    let schema = Schema::parse_str(
+        r#"{
+            "type": "record",
+            "name": "event",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "ts", "type": {"type": "long", "logicalType": "timestamp-millis"}}
+            ]
+        }"#,
+    )
+    .unwrap();
+    let mut writer = Writer::new(&schema, Vec::new());
+    let mut record = Record::new(&schema).unwrap();
+    record.put("id", 1i64);
+    record.put("ts", Value::TimestampMillis(1_700_000_000_000));
+    writer.append(record).unwrap();
+    fs::write(&path, writer.into_inner().unwrap()).unwrap();
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args(["schema", path.to_str().unwrap(), "--native"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r#"
+    {
+      "type": "record",
+      "name": "event",
+      "fields": [
+        {
+          "name": "id",
+          "type": "long"
+        },
+        {
+          "name": "ts",
+          "type": {
+            "type": "long",
+            "logicalType": "timestamp-millis"
+          }
+        }
+      ]
+    }
+    "#);
+}
+
+const NESTED_EVENTS: &str = r#"{"id":1,"user":{"name":"ann","geo":{"city":"Oslo"}},"tags":["a","b"]}
+{"id":2,"user":{"name":"bob","geo":{"city":"Rome"}},"tags":["c"]}
+{"id":3,"user":null,"tags":null}
+"#;
+
+#[test]
+fn test_flatten_structs_with_separator_and_max_depth() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "events.json", NESTED_EVENTS);
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "flatten",
+            input.to_str().unwrap(),
+            "--separator",
+            "_",
+            "--max-depth",
+            "1",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +----+--------+--------------+-----------+
+    | id | tags   | user_geo     | user_name |
+    +----+--------+--------------+-----------+
+    | 1  | [a, b] | {city: Oslo} | ann       |
+    | 2  | [c]    | {city: Rome} | bob       |
+    | 3  |        |              |           |
+    +----+--------+--------------+-----------+
+    ");
+}
+
+#[test]
+fn test_flatten_explode_lists() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "events.json", NESTED_EVENTS);
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args(["flatten", input.to_str().unwrap(), "--explode", "tags"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +----+------+---------------+-----------+
+    | id | tags | user.geo.city | user.name |
+    +----+------+---------------+-----------+
+    | 1  | a    | Oslo          | ann       |
+    | 1  | b    | Oslo          | ann       |
+    | 2  | c    | Rome          | bob       |
+    | 3  |      |               |           |
+    +----+------+---------------+-----------+
+    ");
+}
+
+#[test]
+fn test_flatten_explode_keeps_empty_lists() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "events.json",
+        "{\"id\": 1, \"tags\": [\"a\", \"b\"]}\n\
+         {\"id\": 2, \"tags\": []}\n\
+         {\"id\": 3, \"tags\": null}\n",
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args(["flatten", input.to_str().unwrap(), "--explode", "tags"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +----+------+
+    | id | tags |
+    +----+------+
+    | 1  | a    |
+    | 1  | b    |
+    | 2  |      |
+    | 3  |      |
+    +----+------+
+    ");
+}
+
+#[test]
+fn test_nest_restores_flattened_structs() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "events.json", NESTED_EVENTS);
+    let flat = temp.path().join("flat.parquet");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "flatten",
+            input.to_str().unwrap(),
+            "--output",
+            flat.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args(["nest", flat.to_str().unwrap()])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +----+--------+--------------------------------+
+    | id | tags   | user                           |
+    +----+--------+--------------------------------+
+    | 1  | [a, b] | {geo: {city: Oslo}, name: ann} |
+    | 2  | [c]    | {geo: {city: Rome}, name: bob} |
+    | 3  |        | {geo: {city: }, name: }        |
+    +----+--------+--------------------------------+
+    ");
+}
+
+#[test]
+fn test_view_json_array_and_pretty_printed_records() {
+    let temp = tempdir().unwrap();
+    let array = write_temp_file(
+        temp.path(),
+        "array.json",
+        "[\n  {\"id\": 1, \"name\": \"a\"},\n  {\"id\": 2, \"name\": \"b\"}\n]\n",
+    );
+    let pretty = write_temp_file(
+        temp.path(),
+        "pretty.json",
+        "{\n  \"id\": 3,\n  \"name\": \"c\"\n}\n{\n  \"id\": 4,\n  \"name\": \"d\"\n}\n",
+    );
+
+    let mut output = String::new();
+    for input in [array, pretty] {
+        let stdout = Command::cargo_bin("dfkit")
+            .unwrap()
+            .args(["view", input.to_str().unwrap()])
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        output.push_str(&String::from_utf8(stdout).unwrap());
+    }
+
+    assert_snapshot!(output, @r"
+    +----+------+
+    | id | name |
+    +----+------+
+    | 1  | a    |
+    | 2  | b    |
+    +----+------+
+    +----+------+
+    | id | name |
+    +----+------+
+    | 3  | c    |
+    | 4  | d    |
+    +----+------+
+    ");
+}
+
+#[test]
+fn test_json_types_override_reads_numbers_as_strings() {
+    let temp = tempdir().unwrap();
+    let array = write_temp_file(
+        temp.path(),
+        "array.json",
+        "[{\"id\": 1, \"ok\": true}, {\"id\": 20, \"ok\": false}]\n",
+    );
+    let lines = write_temp_file(
+        temp.path(),
+        "lines.json",
+        "{\"id\": 1, \"ok\": true}\n{\"id\": 20, \"ok\": false}\n",
+    );
+
+    let mut output = String::new();
+    for input in [array, lines] {
+        let stdout = Command::cargo_bin("dfkit")
+            .unwrap()
+            .args([
+                "query",
+                input.to_str().unwrap(),
+                "--types",
+                "id:Utf8,ok:Utf8",
+                "--sql",
+                "SELECT id || '!' AS id, ok FROM t ORDER BY id",
+            ])
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        output.push_str(&String::from_utf8(stdout).unwrap());
+    }
+
+    assert_snapshot!(output, @r"
+    +-----+-------+
+    | id  | ok    |
+    +-----+-------+
+    | 1!  | true  |
+    | 20! | false |
+    +-----+-------+
+    +-----+-------+
+    | id  | ok    |
+    +-----+-------+
+    | 1!  | true  |
+    | 20! | false |
+    +-----+-------+
+    ");
+}
+
+#[test]
+fn test_query_json_path_selects_wrapped_records() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "pages.json",
+        r#"{"data": {"items": [{"id": 1}, {"id": 2}]}, "next": "page2"}
+{"data": {"items": [{"id": 3}]}, "next": null}
+"#,
+    );
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "query",
+            input.to_str().unwrap(),
+            "--sql",
+            "SELECT sum(id) AS total FROM t",
+            "--json-path",
+            "data.items",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +-------+
+    | total |
+    +-------+
+    | 6     |
+    +-------+
+    ");
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    cmd.args([
+        "view",
+        input.to_str().unwrap(),
+        "--json-path",
+        "data.missing",
+    ])
+    .assert()
+    .failure()
+    .stderr(predicates::str::contains(
+        "JSON path data.missing not found",
+    ));
+}
+
+#[test]
+fn test_convert_json_array_output() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "input.csv", "id,name\n1,a\n2,b\n");
+    let output = temp.path().join("output.json");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "convert",
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "--json-array",
+        ])
+        .assert()
+        .success();
+
+    let written = fs::read_to_string(&output).unwrap();
+    assert_eq!(written, r#"[{"id":1,"name":"a"},{"id":2,"name":"b"}]"#);
+}
+
+#[test]
+fn test_view_excel_sheet_detects_header_below_title() {
+    use rust_xlsxwriter::Workbook;
+
+    let temp = tempdir().unwrap();
+    let path = temp.path().join("report.xlsx");
+    let mut workbook = Workbook::new();
+    workbook
+        .add_worksheet()
+        .set_name("Summary")
+        .unwrap()
+        .write_string(0, 0, "See the Data sheet")
+        .unwrap();
+    let data = workbook.add_worksheet().set_name("Data").unwrap();
+    data.write_string(0, 0, "Quarterly sales").unwrap();
+    for (col, header) in ["region", "units", "revenue"].iter().enumerate() {
+        data.write_string(2, col as u16, *header).unwrap();
+    }
+    for (row, (region, units, revenue)) in
+        [("north", 3, 10.5), ("south", 5, 20.0)].iter().enumerate()
+    {
+        let row = row as u32 + 3;
+        data.write_string(row, 0, *region).unwrap();
+        data.write_number(row, 1, *units).unwrap();
+        data.write_number(row, 2, *revenue).unwrap();
+    }
+    workbook.save(&path).unwrap();
+
+    let mut output = String::new();
+    for args in [
+        vec!["--sheet", "Data"],
+        vec!["--sheet", "1", "--range", "A3:B5"],
+    ] {
+        let stdout = Command::cargo_bin("dfkit")
+            .unwrap()
+            .args(["schema", path.to_str().unwrap()])
+            .args(&args)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        output.push_str(&String::from_utf8(stdout).unwrap());
+    }
+
+    assert_snapshot!(output, @r"
+    +-------------+-----------+-------------+
+    | column_name | data_type | is_nullable |
+    +-------------+-----------+-------------+
+    | region      | Utf8      | YES         |
+    | units       | Int64     | YES         |
+    | revenue     | Float64   | YES         |
+    +-------------+-----------+-------------+
+    +-------------+-----------+-------------+
+    | column_name | data_type | is_nullable |
+    +-------------+-----------+-------------+
+    | region      | Utf8      | YES         |
+    | units       | Int64     | YES         |
+    +-------------+-----------+-------------+
+    ");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args(["view", path.to_str().unwrap(), "--sheet", "Missing"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Sheet Missing not found, available sheets: Summary, Data",
+        ));
+}
+
+#[test]
+fn test_convert_csv_to_xlsx_round_trip() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(
+        temp.path(),
+        "input.csv",
+        "id,name,amount,active\n1,a,1.5,true\n2,,2.25,false\n",
+    );
+    let output = temp.path().join("output.xlsx");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args(["convert", input.to_str().unwrap(), output.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let stdout = cmd
+        .args(["view", output.to_str().unwrap()])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(stdout).unwrap(), @r"
+    +----+------+--------+--------+
+    | id | name | amount | active |
+    +----+------+--------+--------+
+    | 1  | a    | 1.5    | true   |
+    | 2  |      | 2.25   | false  |
+    +----+------+--------+--------+
+    ");
+}
+
+#[test]
+fn test_convert_xlsx_keeps_large_integers() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "input.csv", "id\n9007199254740993\n-42\n");
+    let output = temp.path().join("output.xlsx");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args(["convert", input.to_str().unwrap(), output.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let stdout = cmd
+        .args(["view", output.to_str().unwrap()])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(stdout).unwrap(), @r"
+    +------------------+
+    | id               |
+    +------------------+
+    | 9007199254740993 |
+    | -42              |
+    +------------------+
+    ");
+}
+
+#[test]
+fn test_convert_xlsx_row_limit() {
+    let temp = tempdir().unwrap();
+    let mut contents = String::from("id\n");
+    for i in 0..1_048_576 {
+        contents.push_str(&format!("{}\n", i));
+    }
+    let input = write_temp_file(temp.path(), "input.csv", &contents);
+    let output = temp.path().join("output.xlsx");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args(["convert", input.to_str().unwrap(), output.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Output has more rows than a spreadsheet can hold",
+        ));
+    assert!(!output.exists());
+}
+
+#[test]
+fn test_view_sqlite_table_maps_declared_types() {
+    let temp = tempdir().unwrap();
+    let path = temp.path().join("app.db");
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE orders (id INTEGER, customer VARCHAR(20), amount DECIMAL(10,2), \
+         paid BOOLEAN, created DATETIME);
+         INSERT INTO orders VALUES (1, 'ann', 10.5, 1, '2024-01-02 10:30:00');
+         INSERT INTO orders VALUES (2, 'bob', NULL, 0, '2024-01-03 08:00:00');
+         CREATE TABLE customers (name TEXT);",
+    )
+    .unwrap();
+    drop(conn);
+
+    let mut output = String::new();
+    let source = format!("sqlite://{}?table=orders", path.display());
+    for args in [
+        vec!["view", source.as_str()],
+        vec!["schema", path.to_str().unwrap(), "--table", "orders"],
+    ] {
+        let stdout = Command::cargo_bin("dfkit")
+            .unwrap()
+            .args(&args)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        output.push_str(&String::from_utf8(stdout).unwrap());
+    }
+
+    assert_snapshot!(output, @r"
+    +----+----------+--------+-------+---------------------+
+    | id | customer | amount | paid  | created             |
+    +----+----------+--------+-------+---------------------+
+    | 1  | ann      | 10.5   | true  | 2024-01-02T10:30:00 |
+    | 2  | bob      |        | false | 2024-01-03T08:00:00 |
+    +----+----------+--------+-------+---------------------+
+    +-------------+------------------------------+-------------+
+    | column_name | data_type                    | is_nullable |
+    +-------------+------------------------------+-------------+
+    | id          | Int64                        | YES         |
+    | customer    | Utf8                         | YES         |
+    | amount      | Float64                      | YES         |
+    | paid        | Boolean                      | YES         |
+    | created     | Timestamp(Millisecond, None) | YES         |
+    +-------------+------------------------------+-------------+
+    ");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args(["view", path.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "available tables: customers, orders",
+        ));
+}
+
+#[test]
+fn test_convert_csv_appends_to_sqlite_table() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "input.csv", "id,name\n1,a\n2,b\n");
+    let db = temp.path().join("out.sqlite");
+
+    for _ in 0..2 {
+        Command::cargo_bin("dfkit")
+            .unwrap()
+            .args([
+                "convert",
+                input.to_str().unwrap(),
+                db.to_str().unwrap(),
+                "--table",
+                "people",
+            ])
+            .assert()
+            .success();
+    }
+
+    let conn = rusqlite::Connection::open(&db).unwrap();
+    let (rows, ids): (i64, i64) = conn
+        .query_row("SELECT count(*), sum(id) FROM people", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!((rows, ids), (4, 6));
+}
+
+#[test]
+fn test_cast_to_existing_sqlite_database() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "input.csv", "a\n1\n2\n");
+    let bad = write_temp_file(temp.path(), "bad.csv", "a\n3\nabc\n");
+    let db = temp.path().join("keep.sqlite");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args(["convert", input.to_str().unwrap(), db.to_str().unwrap()])
+        .assert()
+        .success();
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "cast",
+            input.to_str().unwrap(),
+            "--types",
+            "a:Int32",
+            "-o",
+            db.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    // A failed cast rolls back the rows it already wrote
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "cast",
+            bad.to_str().unwrap(),
+            "--types",
+            "a:Int32",
+            "--batch-size",
+            "1",
+            "-o",
+            db.to_str().unwrap(),
+        ])
+        .assert()
+        .failure();
+
+    let conn = rusqlite::Connection::open(&db).unwrap();
+    let (rows, total): (i64, i64) = conn
+        .query_row("SELECT count(*), sum(a) FROM keep", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!((rows, total), (4, 6));
+}
+
+#[test]
+fn test_unsupported_output_keeps_existing_file() {
+    let temp = tempdir().unwrap();
+    let input = write_temp_file(temp.path(), "input.csv", "a\n1\n");
+    let output = write_temp_file(temp.path(), "keep.avro", "existing");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "cast",
+            input.to_str().unwrap(),
+            "--types",
+            "a:Int32",
+            "-o",
+            output.to_str().unwrap(),
+        ])
+        .assert()
+        .failure();
+
+    assert_eq!(fs::read_to_string(&output).unwrap(), "existing");
+    assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 2);
+}
+
+fn write_fixed_width_files(dir: &Path) -> (PathBuf, PathBuf) {
+    let data = write_temp_file(
+        dir,
+        "people.fwf",
+        "0001Ann       2024-01-02  10.50\n\
+         0002Bob       2024-01-03       \n\
+         \n\
+         0003Christina 2024-01-04 125.00\n",
+    );
+    let layout = write_temp_file(
+        dir,
+        "layout.json",
+        r#"{"columns": [
+            {"name": "id", "start": 1, "length": 4, "type": "Int64"},
+            {"name": "name", "start": 5, "length": 10},
+            {"name": "joined", "start": 15, "length": 10, "type": "Date32"},
+            {"name": "balance", "start": 25, "length": 7, "type": "Float64"}
+        ]}"#,
+    );
+    (data, layout)
+}
+
+#[test]
+fn test_view_fixed_width_with_layout() {
+    let temp = tempdir().unwrap();
+    let (data, layout) = write_fixed_width_files(temp.path());
+
+    let mut output = String::new();
+    for command in ["view", "schema"] {
+        let stdout = Command::cargo_bin("dfkit")
+            .unwrap()
+            .args([
+                command,
+                data.to_str().unwrap(),
+                "--layout",
+                layout.to_str().unwrap(),
+            ])
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        output.push_str(&String::from_utf8(stdout).unwrap());
+    }
+
+    assert_snapshot!(output, @r"
+    +----+-----------+------------+---------+
+    | id | name      | joined     | balance |
+    +----+-----------+------------+---------+
+    | 1  | Ann       | 2024-01-02 | 10.5    |
+    | 2  | Bob       | 2024-01-03 |         |
+    | 3  | Christina | 2024-01-04 | 125.0   |
+    +----+-----------+------------+---------+
+    +-------------+-----------+-------------+
+    | column_name | data_type | is_nullable |
+    +-------------+-----------+-------------+
+    | id          | Int64     | YES         |
+    | name        | Utf8      | YES         |
+    | joined      | Date32    | YES         |
+    | balance     | Float64   | YES         |
+    +-------------+-----------+-------------+
+    ");
+}
+
+#[test]
+fn test_query_fixed_width_with_layout_ignores_extension() {
+    let temp = tempdir().unwrap();
+    let (data, layout) = write_fixed_width_files(temp.path());
+    let extract = temp.path().join("extract.txt");
+    fs::copy(&data, &extract).unwrap();
+
+    let mut cmd = Command::cargo_bin("dfkit").unwrap();
+    let output = cmd
+        .args([
+            "query",
+            extract.to_str().unwrap(),
+            "--layout",
+            layout.to_str().unwrap(),
+            "--sql",
+            "SELECT id, name FROM t",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_snapshot!(String::from_utf8(output).unwrap(), @r"
+    +----+-----------+
+    | id | name      |
+    +----+-----------+
+    | 1  | Ann       |
+    | 2  | Bob       |
+    | 3  | Christina |
+    +----+-----------+
+    ");
+}
+
+#[test]
+fn test_convert_fixed_width_to_csv() {
+    let temp = tempdir().unwrap();
+    let (data, layout) = write_fixed_width_files(temp.path());
+    let out = temp.path().join("people.csv");
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "convert",
+            data.to_str().unwrap(),
+            out.to_str().unwrap(),
+            "--layout",
+            layout.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    assert_snapshot!(fs::read_to_string(out).unwrap(), @r"
+    id,name,joined,balance
+    1,Ann,2024-01-02,10.5
+    2,Bob,2024-01-03,
+    3,Christina,2024-01-04,125.0
+    ");
+}
+
+#[test]
+fn test_view_fixed_width_requires_layout() {
+    let temp = tempdir().unwrap();
+    let (data, _) = write_fixed_width_files(temp.path());
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args(["view", data.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Fixed-width files need a --layout file",
+        ));
+}
+
+#[test]
+fn test_view_fixed_width_bad_value_names_line_and_column() {
+    let temp = tempdir().unwrap();
+    let (_, layout) = write_fixed_width_files(temp.path());
+    let data = write_temp_file(
+        temp.path(),
+        "bad.fwf",
+        "0001Ann       2024-01-02  10.50\n\
+         \n\
+         0x2 Bob       2024-01-03       \n",
+    );
+
+    Command::cargo_bin("dfkit")
+        .unwrap()
+        .args([
+            "view",
+            data.to_str().unwrap(),
+            "--layout",
+            layout.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Cannot cast value '0x2' in column id on line 3 to Int64",
+        ));
+}
@@ -1,8 +1,11 @@
+use datafusion::arrow::datatypes::{DataType, TimeUnit};
 use datafusion::error::DataFusionError;
 use datafusion::prelude::{CsvReadOptions, SessionContext};
 use dfkit::utils::{
-    DfKitError, FileFormat, FileParseError, download_to_tempfile, file_type, parse_file_list,
-    register_table, write_output,
+    DfKitError, FileFormat, FileParseError, NameStyle, download_to_tempfile, file_type,
+    matches_pattern, normalize_column_names, parse_byte_size, parse_cell_range, parse_file_list,
+    parse_sort_spec, parse_sqlite_source, parse_type_overrides, register_table, render_template,
+    write_output,
 };
 use std::fs::File;
 use std::path::{Path, PathBuf};
@@ -30,12 +33,27 @@ fn test_file_type_supported() {
     );
     assert_eq!(file_type(Path::new("file.json")).unwrap(), FileFormat::Json);
     assert_eq!(file_type(Path::new("file.avro")).unwrap(), FileFormat::Avro);
+    assert_eq!(
+        file_type(Path::new("file.xlsx")).unwrap(),
+        FileFormat::Excel
+    );
+    assert_eq!(file_type(Path::new("file.ods")).unwrap(), FileFormat::Excel);
+    assert_eq!(
+        file_type(Path::new("sqlite:///data/app.db?table=orders")).unwrap(),
+        FileFormat::Sqlite
+    );
+    assert_eq!(
+        file_type(Path::new("file.fwf")).unwrap(),
+        FileFormat::FixedWidth
+    );
 }
 
 #[test]
 fn test_file_type_unsupported() {
     let err = file_type(Path::new("file.txt")).unwrap_err();
     assert!(matches!(err, FileParseError::UnsupportedFileFormat));
+    let err = file_type(Path::new("file.dat")).unwrap_err();
+    assert!(matches!(err, FileParseError::UnsupportedFileFormat));
 }
 
 #[test]
@@ -109,7 +127,7 @@ async fn test_write_output_and_read_back() {
         .unwrap_err();
     assert!(matches!(
         err,
-        DfKitError::DataFusion(DataFusionError::NotImplemented(_))
+        DfKitError::DataFusion(e) if matches!(*e, DataFusionError::NotImplemented(_))
     ));
 }
 
@@ -135,3 +153,127 @@ async fn test_register_table_csv() {
     assert_eq!(batch.num_rows(), 2);
     assert_eq!(batch.num_columns(), 2);
 }
+
+#[test]
+fn test_matches_pattern() {
+    assert!(matches_pattern("m_*", "m_clicks"));
+    assert!(matches_pattern("*_id", "customer_id"));
+    assert!(matches_pattern("col?", "col1"));
+    assert!(!matches_pattern("m_*", "date"));
+    assert!(!matches_pattern("col?", "col10"));
+}
+
+#[test]
+fn test_parse_byte_size() {
+    assert_eq!(parse_byte_size("1024").unwrap(), 1024);
+    assert_eq!(parse_byte_size("256MB").unwrap(), 256 * 1024 * 1024);
+    assert_eq!(parse_byte_size("4GB").unwrap(), 4 * 1024 * 1024 * 1024);
+    assert_eq!(parse_byte_size("1.5kb").unwrap(), 1536);
+    assert!(parse_byte_size("lots").is_err());
+}
+
+#[test]
+fn test_render_template() {
+    assert_eq!(
+        render_template("{stem}_{n:04}.{ext}", "data", "csv", Some(7), None).unwrap(),
+        "data_0007.csv"
+    );
+    assert_eq!(
+        render_template("{stem}_{n:0}.{ext}", "data", "csv", Some(7), None).unwrap(),
+        "data_7.csv"
+    );
+    assert_eq!(
+        render_template("{stem}_{value}.{ext}", "orders", "parquet", None, Some("acme")).unwrap(),
+        "orders_acme.parquet"
+    );
+    assert!(render_template("{stem}_{value}.{ext}", "orders", "csv", Some(1), None).is_err());
+}
+
+#[test]
+fn test_parse_type_overrides() {
+    let fields = parse_type_overrides("zip:Utf8, amount:Decimal128(18,2),ts:Timestamp(ms)").unwrap();
+    assert_eq!(fields.len(), 3);
+    assert_eq!(fields[0].name(), "zip");
+    assert_eq!(fields[0].data_type(), &DataType::Utf8);
+    assert_eq!(fields[1].data_type(), &DataType::Decimal128(18, 2));
+    assert_eq!(
+        fields[2].data_type(),
+        &DataType::Timestamp(TimeUnit::Millisecond, None)
+    );
+
+    assert!(parse_type_overrides("zip").is_err());
+    assert!(parse_type_overrides("zip:NotAType").is_err());
+}
+
+#[test]
+fn test_normalize_column_names() {
+    let names = vec![
+        "Customer Name (US)".to_string(),
+        "orderID".to_string(),
+        "customer_name_us".to_string(),
+        "2020 Sales".to_string(),
+        "???".to_string(),
+    ];
+    assert_eq!(
+        normalize_column_names(&names, NameStyle::SnakeCase),
+        vec![
+            "customer_name_us",
+            "order_id",
+            "customer_name_us_2",
+            "_2020_sales",
+            "column"
+        ]
+    );
+    assert_eq!(
+        normalize_column_names(&names[..2], NameStyle::Lower),
+        vec!["customer_name_us", "orderid"]
+    );
+    assert_eq!(
+        normalize_column_names(&names[..2], NameStyle::Upper),
+        vec!["CUSTOMER_NAME_US", "ORDERID"]
+    );
+}
+
+#[test]
+fn test_parse_sort_spec() {
+    let spec = parse_sort_spec("revenue desc nulls last", false).unwrap();
+    assert_eq!(spec.column, "revenue");
+    assert!(!spec.ascending);
+    assert!(!spec.nulls_first);
+
+    let spec = parse_sort_spec(" Order Date ASC NULLS FIRST", true).unwrap();
+    assert_eq!(spec.column, "Order Date");
+    assert!(spec.ascending);
+    assert!(spec.nulls_first);
+
+    // Without an explicit direction the global --descending flag applies
+    assert!(!parse_sort_spec("region", true).unwrap().ascending);
+    assert!(parse_sort_spec("region", false).unwrap().ascending);
+
+    assert!(parse_sort_spec("desc", false).is_err());
+    assert!(parse_sort_spec("region nulls sometimes", false).is_err());
+}
+
+#[test]
+fn test_parse_cell_range() {
+    assert_eq!(parse_cell_range("A1:D20").unwrap(), ((0, 0), (19, 3)));
+    assert_eq!(parse_cell_range("b3:aa10").unwrap(), ((2, 1), (9, 26)));
+
+    assert!(parse_cell_range("A1").is_err());
+    assert!(parse_cell_range("1:C3").is_err());
+    assert!(parse_cell_range("A0:C3").is_err());
+    assert!(parse_cell_range("D4:A1").is_err());
+}
+
+#[test]
+fn test_parse_sqlite_source() {
+    assert_eq!(
+        parse_sqlite_source(Path::new("sqlite:///data/app.db?table=orders")).unwrap(),
+        (PathBuf::from("/data/app.db"), Some("orders".to_string()))
+    );
+    assert_eq!(
+        parse_sqlite_source(Path::new("/data/app.sqlite")).unwrap(),
+        (PathBuf::from("/data/app.sqlite"), None)
+    );
+    assert!(parse_sqlite_source(Path::new("sqlite://app.db")).is_err());
+}